@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::cmp::Eq;
 use std::iter::FromIterator;
-use crate::Value;
+use super::value::Value;
 
 impl Eq for Value {}
 impl Hash for Value {
@@ -13,7 +13,9 @@ impl Hash for Value {
             Value::String(n) => { n.hash(state); }
             Value::Boolean(n) => { n.hash(state); }
             Value::Dict(n) => { n.iter().collect::<Vec<(&Value, &Value)>>().hash(state); }
-            Value::Undefined | Value::Empty | Value::Ref(_) => { 0.hash(state); }
+            // hashed as the value it points to, which it's equal to
+            Value::Ref(n) => { n.clone().hash(state); }
+            Value::Undefined | Value::Empty | Value::Quote(_) => { 0.hash(state); }
         }
     }
 }
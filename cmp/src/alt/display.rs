@@ -10,6 +10,7 @@ impl fmt::Display for Value {
             Value::Dict(d) => write!(f, "{d:?}"),
             Value::Arr(x) => write!(f, "{x:?}"),
             Value::Ref(x) => write!(f, "{}", x.clone()),
+            Value::Quote(_) => write!(f, "quotation"),
             _ => write!(f, "undefined")
         }
     }
@@ -24,6 +25,7 @@ impl fmt::Debug for Value {
                 Value::Dict(d) => write!(f, "{d:#?}"),
                 Value::Arr(x) => write!(f, "{x:#?}"),
                 Value::Ref(x) => write!(f, "&{:#?}", x.clone()),
+                Value::Quote(_) => write!(f, "quotation"),
                 _ => write!(f, "undefined")
             }
         }
@@ -33,6 +35,7 @@ impl fmt::Debug for Value {
             Value::Dict(d) => write!(f, "{d:?}"),
             Value::Arr(x) => write!(f, "{x:?}"),
             Value::Ref(x) => write!(f, "{:?}", x.clone()),
+            Value::Quote(_) => write!(f, "quotation"),
             _ => write!(f, "undefined")
         }
     }
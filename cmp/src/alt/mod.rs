@@ -3,4 +3,6 @@ pub mod ops;
 pub mod display;
 pub mod stack;
 pub mod collections;
-pub mod r#ref;
\ No newline at end of file
+pub mod r#ref;
+pub mod quote;
+pub mod seq;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::value::Value;
+use super::{value::Value, r#ref::Ref};
 use std::{collections::hash_map::Entry, iter::Sum, ops::{Add, Div, Index, IndexMut, Mul, Rem, Shl, Shr, Sub}, sync::{Arc, Mutex, MutexGuard}};
 
 macro_rules! arr_op {
@@ -21,7 +21,8 @@ macro_rules! join_arr {
 impl Add for Value {
     type Output = Self;
     fn add (self, rhs: Self) -> Self {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, +, b); }
@@ -41,14 +42,15 @@ impl Add for Value {
             _ => {}
         }
 
-        Value::Number(self.cast_float() + rhs.cast_float())
+        Value::Number(lhs.cast_float() + rhs.cast_float())
     }
 }
 
 impl Sub for Value {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, -, b); }
@@ -66,14 +68,15 @@ impl Sub for Value {
             _ => {}
         }
 
-        Value::Number(self.cast_float() - rhs.cast_float())
+        Value::Number(lhs.cast_float() - rhs.cast_float())
     }    
 }
 
 impl Mul for Value {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, *, b); }
@@ -84,14 +87,15 @@ impl Mul for Value {
             _ => {}
         }
 
-        Value::Number(self.cast_float() * rhs.cast_float())
+        Value::Number(lhs.cast_float() * rhs.cast_float())
     }    
 }
 
 impl Div for Value {
     type Output = Self;
     fn div(self, rhs: Self) -> Self {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, /, b); }
@@ -109,14 +113,15 @@ impl Div for Value {
             _ => {}
         }
 
-        Value::Number(self.cast_float() / rhs.cast_float())
+        Value::Number(lhs.cast_float() / rhs.cast_float())
     }    
 }
 
 impl Rem for Value {
     type Output = Self;
     fn rem(self, rhs: Self) -> Self {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, %, b); }
@@ -127,14 +132,15 @@ impl Rem for Value {
             _ => {}
         }
 
-        Value::Number(self.cast_float() % rhs.cast_float())
+        Value::Number(lhs.cast_float() % rhs.cast_float())
     }    
 }
 
 impl Shl for Value {
     type Output = Self;
     fn shl(self, rhs: Self) -> Self::Output {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, <<, b); }
@@ -145,14 +151,15 @@ impl Shl for Value {
             _ => {}
         }
 
-        Value::Number(((self.cast_float() as i64) << (rhs.cast_float() as i64)) as f64)
+        Value::Number((lhs.cast_float() as i64).wrapping_shl(rhs.cast_float() as i64 as u32) as f64)
     }
 }
 
 impl Shr for Value {
     type Output = Self;
     fn shr(self, rhs: Self) -> Self::Output {
-        match &self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        match &lhs {
             Value::Arr(a) => {
                 match rhs {
                     Value::Arr(b) => { arr_op!(a, >>, b); }
@@ -163,13 +170,34 @@ impl Shr for Value {
             _ => {}
         }
 
-        Value::Number(((self.cast_float() as i64) >> (rhs.cast_float() as i64)) as f64)
+        Value::Number((lhs.cast_float() as i64).wrapping_shr(rhs.cast_float() as i64 as u32) as f64)
     }
 }
 
 
+impl Value {
+    pub fn pow (self, rhs: Self) -> Self {
+        let (lhs, rhs) = (self.unref(), rhs.unref());
+        if let (Value::Arr(a), Value::Arr(b)) = (&lhs, &rhs) {
+            let mut res: Vec<Value> = a.iter().zip(b).map(|(a, b)| a.clone().pow(b.clone())).collect();
+            res.extend(a.iter().skip(b.len()).cloned());
+            res.extend(b.iter().skip(a.len()).cloned());
+            return Value::Arr(res)
+        }
+
+        Value::Number(lhs.cast_float().powf(rhs.cast_float()))
+    }
+}
+
 // accessing and changing values in containers (string, array, dictionary)
 
+// index counted from the end when negative, `None` when it's out of bounds
+fn position (len: usize, x: f64) -> Option<usize> {
+    let x = if x < 0.0 { len as f64 + x } else { x };
+    if x < 0.0 || x as usize >= len { return None }
+    Some(x as usize)
+}
+
 impl Index<Value> for Vec<Value> {
     type Output = Value;
 
@@ -183,8 +211,10 @@ impl Index<Value> for Vec<Value> {
                 Box::leak(r)
             }
             Value::Number(x) => {
-                if x < 0.0 { &self[(self.len() as f64 + x) as usize] }
-                else { &self[x as usize] }
+                match position(self.len(), x) {
+                    Some(x) => &self[x],
+                    None => &Value::Undefined
+                }
             },
             _ => { &Value::Undefined }
         }
@@ -208,14 +238,16 @@ impl Index<Value> for String {
         match index {
             Value::Arr(x) => {
                 let mut result = vec![];
-                for i in x { result.push(v[i.cast_float() as usize]); }
+                for i in x { if let Some(i) = position(v.len(), i.cast_float()) { result.push(v[i]); } }
 
                 let r = Box::new(Value::String(result.iter().collect::<String>()));
                 Box::leak(r)
             }
             Value::Number(x) => {
-                let r = Box::new(Value::String(v[x as usize].to_string()));
-                Box::leak(r)
+                match position(v.len(), x) {
+                    Some(x) => Box::leak(Box::new(Value::String(v[x].to_string()))),
+                    None => &Value::Undefined
+                }
             },
             _ => { &Value::Undefined }
         }
@@ -226,8 +258,10 @@ impl Index<Value> for Value {
     type Output = Value;
 
     fn index(&self, index: Value) -> &Self::Output {
+        let index = index.unref();
         match self {
             Value::Arr(x) => return &x[index],
+            Value::Ref(x) => return Box::leak(Box::new(x.clone()[index].clone())),
             Value::String(x) => return &x[index],
             Value::Dict(x) => {
                 if let Some(y) = x.get(&index) { return y; }
@@ -256,23 +290,23 @@ impl IndexMut<Value> for Value {
     }
 }
 
-pub fn set (d: Arc<Mutex<Value>>, index: Value, v: Value) {
+pub fn set (d: Ref, index: Value, v: Value) {
     match index {
         Value::Arr(x) => {
-            if let Value::Dict(_) = d.lock().unwrap().clone() { d.lock().unwrap()[Value::Arr(x)] = v; return; }
-            for i in x { set(d.clone(), i, v.clone()); }
+            let is_dict = matches!(*d.lock(), Value::Dict(_));
+            if is_dict { d.lock()[Value::Arr(x)] = v; return; }
+            for i in x { set(d.clone_ref(), i, v.clone()); }
         }
         _ => {
-            if let Value::Arr(mut a) = d.lock().unwrap().clone() {
-                if let Value::Number(x) = index {
-                    let x = x as usize;
-                    if x >= a.len() {
-                        a.resize_with(x+1, || Value::Undefined);
-                    }
+            let mut d = d.lock();
+            if let (Value::Arr(a), Value::Number(x)) = (&mut *d, &index) {
+                let x = *x as usize;
+                if x >= a.len() {
+                    a.resize_with(x+1, || Value::Undefined);
                 }
             }
 
-            d.lock().unwrap()[index] = v;
+            d[index] = v;
         }
     }
 }
@@ -281,7 +315,7 @@ pub fn set (d: Arc<Mutex<Value>>, index: Value, v: Value) {
 
 impl Sum<Value> for Value {
     fn sum<I: Iterator<Item = Value>>(iter: I) -> Self {
-        iter.fold(Value::Undefined, |a, b| a + b)
+        iter.fold(Value::Number(0.0), |a, b| a + b)
     }
 }
 
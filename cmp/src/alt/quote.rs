@@ -0,0 +1,37 @@
+use std::rc::Rc;
+use super::{value::Value, stack::push};
+
+// code as a value, created with `do {...}` and executed with `call`
+#[derive(Clone)]
+pub struct Quote {
+    f: Rc<dyn Fn(&mut Vec<Value>)>
+}
+
+impl Quote {
+
+    pub fn new (f: impl Fn(&mut Vec<Value>) + 'static) -> Self {
+        Self { f: Rc::new(f) }
+    }
+
+    pub fn call (&self, stack: &mut Vec<Value>) {
+        (self.f)(stack)
+    }
+
+}
+
+// quotation which pushes `x` and runs `q`
+pub fn curry (x: Value, q: Value) -> Value {
+    Value::Quote(Quote::new(move |stack| { push(stack, x.clone()); q.clone().call(stack) }))
+}
+
+// quotation which runs `p` and then `q`
+pub fn compose (p: Value, q: Value) -> Value {
+    Value::Quote(Quote::new(move |stack| { p.clone().call(stack); q.clone().call(stack) }))
+}
+
+// quotations are equal only if they are the same value
+impl PartialEq for Quote {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::as_ptr(&self.f) as *const () == Rc::as_ptr(&other.f) as *const ()
+    }
+}
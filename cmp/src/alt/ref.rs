@@ -1,5 +1,5 @@
 use std::sync::{Arc, Mutex, MutexGuard};
-use crate::Value;
+use super::value::Value;
 
 #[derive(Debug, Clone)]
 pub struct Ref {
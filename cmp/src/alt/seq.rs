@@ -0,0 +1,64 @@
+use super::{value::Value, stack::push};
+
+// higher-order words over arrays. quotation runs on the stack with the element pushed on top of it
+
+// result of quotation for the element, undefined if it leaves nothing
+fn apply (q: &Value, x: Value, stack: &mut Vec<Value>) -> Value {
+    push(stack, x);
+    q.clone().call(stack);
+    stack.pop().unwrap_or(Value::Undefined)
+}
+
+pub fn map (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().map(|x| apply(&q, x, stack)).collect();
+    push(stack, Value::Arr(x))
+}
+
+pub fn filter (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().filter(|x| apply(&q, x.clone(), stack).cast_bool()).collect();
+    push(stack, Value::Arr(x))
+}
+
+pub fn reduce (arr: Value, init: Value, q: Value, stack: &mut Vec<Value>) {
+    push(stack, init);
+    for x in arr.cast_vec() {
+        push(stack, x);
+        q.clone().call(stack);
+    }
+}
+
+pub fn each (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    for x in arr.cast_vec() {
+        push(stack, x);
+        q.clone().call(stack);
+    }
+}
+
+pub fn each_index (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    for (i, x) in arr.cast_vec().into_iter().enumerate() {
+        push(stack, x);
+        push(stack, Value::Number(i as f64));
+        q.clone().call(stack);
+    }
+}
+
+pub fn any (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().any(|x| apply(&q, x, stack).cast_bool());
+    push(stack, Value::Boolean(x))
+}
+
+pub fn all (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().all(|x| apply(&q, x, stack).cast_bool());
+    push(stack, Value::Boolean(x))
+}
+
+// first element satisfying the quotation, or undefined
+pub fn find (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().find(|x| apply(&q, x.clone(), stack).cast_bool());
+    push(stack, x.unwrap_or(Value::Undefined))
+}
+
+pub fn count (arr: Value, q: Value, stack: &mut Vec<Value>) {
+    let x = arr.cast_vec().into_iter().filter(|x| apply(&q, x.clone(), stack).cast_bool()).count();
+    push(stack, Value::Number(x as f64))
+}
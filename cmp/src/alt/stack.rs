@@ -1,4 +1,4 @@
-use crate::Value;
+use super::value::Value;
 
 pub fn pop (stack: &mut Vec<Value>) -> Value {
     return stack.pop().expect("ERROR: stack underflow")
@@ -1,9 +1,9 @@
 use std::{cell::RefCell, collections::HashMap, sync::{Arc, Mutex}};
-use super::r#ref::Ref;
+use super::{r#ref::Ref, quote::Quote};
 
-#[derive(PartialEq, Clone)]
+#[derive(Clone)]
 pub enum Value {
-    String(String), Number(f64), Boolean(bool), Arr(Vec<Value>), Dict(HashMap<Value, Value>), Ref(Ref), Undefined, Empty
+    String(String), Number(f64), Boolean(bool), Arr(Vec<Value>), Dict(HashMap<Value, Value>), Ref(Ref), Quote(Quote), Undefined, Empty
 }
 
 impl Value {
@@ -64,10 +64,28 @@ impl Value {
             Value::Boolean(_) => "boolean",
             Value::Dict(_) => "dictionary",
             Value::Ref(x) => return format!("reference>{}", x.clone().cast_type()),
+            Value::Quote(_) => "quotation",
             _ => "undefined"
         }.to_string()
     }
 
+    // value behind references, which operators use
+    pub fn unref (self) -> Value {
+        match self {
+            Value::Ref(x) => x.clone().unref(),
+            x => x
+        }
+    }
+
+    // executes quotation on the stack
+    pub fn call (self, stack: &mut Vec<Value>) {
+        match self {
+            Value::Quote(x) => x.call(stack),
+            Value::Ref(x) => x.clone().call(stack),
+            x => panic!("ERROR: cannot call {}", x.cast_type())
+        }
+    }
+
     pub fn push (&mut self, value: Value) {
         match self {
             Value::Arr(x) => x.push(value),
@@ -76,9 +94,9 @@ impl Value {
         }
     }
 
+    // adds 1 to numbers, also inside of arrays. other values are left as they are
     pub fn inc (&mut self) {
         match self {
-            Value::String(a) => { *a = a.to_uppercase(); }
             Value::Number(a) => { *a += 1.0; }
             Value::Arr(a) => { a.iter_mut().for_each(|x| x.inc()); }
             Value::Ref(a) => { a.lock().inc(); }
@@ -88,7 +106,6 @@ impl Value {
 
     pub fn dec (&mut self) {
         match self {
-            Value::String(a) => { *a = a.to_lowercase(); }
             Value::Number(a) => { *a -= 1.0; }
             Value::Arr(a) => { a.iter_mut().for_each(|x| x.dec()); }
             Value::Ref(a) => { a.lock().dec(); }
@@ -96,6 +113,7 @@ impl Value {
         }
     }
 
+    // sum of elements as numbers
     pub fn sum (&self) -> Value {
         match self {
             Value::Arr(a) => { a.iter().cloned().sum::<Value>() }
@@ -116,4 +134,22 @@ impl Value {
         for i in a { if let Value::Arr(mut x) = i { b.append(&mut x); continue; } b.push(i); }
         Value::Arr(b)
     }
-}
\ No newline at end of file
+}
+
+// reference is equal to the value it points to
+impl PartialEq for Value {
+    fn eq (&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Ref(a), b) => a.clone() == *b,
+            (a, Value::Ref(b)) => *a == b.clone(),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Arr(a), Value::Arr(b)) => a == b,
+            (Value::Dict(a), Value::Dict(b)) => a == b,
+            (Value::Quote(a), Value::Quote(b)) => a == b,
+            (Value::Undefined, Value::Undefined) | (Value::Empty, Value::Empty) => true,
+            _ => false
+        }
+    }
+}
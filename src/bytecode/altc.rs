@@ -0,0 +1,401 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+use super::ir::{BinOp, Body, Expr, ExprKind, Span, Stmt, StmtKind};
+
+/* Binary form of the IR (`.altc`), which is loaded without lexing and generating the program again.
+Header is `ALTC`, version of the format (u16) and FNV-1a checksum (u32) of the rest: pool of strings (names, strings,
+files of spans), pool of numbers, and the program. Nodes are tags followed by their parts, strings and numbers are
+indices in the pools. Integers are LEB128, numbers in the pool are little-endian f64. Statement has its span,
+expression has span only when it differs from the span of the node around it (high bit of its tag is set) */
+
+const MAGIC: &[u8; 4] = b"ALTC";
+// changed with every change of the format, modules of other versions aren't loaded
+const VERSION: u16 = 1;
+
+const HEADER: usize = 10;
+// tag of expression with its own span
+const SPAN: u8 = 0x80;
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    NotModule,
+    Version(u16),
+    Checksum,
+    // bytes which can't be read as a program
+    Corrupt(String)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NotModule => write!(f, "not a compiled module: `.altc` file starts with `ALTC`"),
+            LoadError::Version(x) => write!(f, "module is compiled with format version {x}, but this compiler reads version {VERSION}, compile it again"),
+            LoadError::Checksum => write!(f, "checksum doesn't match, module is damaged"),
+            LoadError::Corrupt(x) => write!(f, "module is damaged: {x}")
+        }
+    }
+}
+
+pub fn encode (body: &Body) -> Vec<u8> {
+    let mut writer = Writer { code: vec![], strings: vec![], string_ids: HashMap::new(), numbers: vec![], number_ids: HashMap::new() };
+    writer.body(body);
+
+    let mut rest = vec![];
+    uint(&mut rest, writer.strings.len());
+    for x in &writer.strings { uint(&mut rest, x.len()); rest.extend(x.as_bytes()); }
+    uint(&mut rest, writer.numbers.len());
+    for x in &writer.numbers { rest.extend(x.to_le_bytes()); }
+    rest.extend(writer.code);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.extend(checksum(&rest).to_le_bytes());
+    bytes.extend(rest);
+    bytes
+}
+
+pub fn decode (bytes: &[u8]) -> Result<Body, LoadError> {
+    if bytes.len() < HEADER || &bytes[..4] != MAGIC { return Err(LoadError::NotModule) }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION { return Err(LoadError::Version(version)) }
+    if u32::from_le_bytes(bytes[6..HEADER].try_into().unwrap()) != checksum(&bytes[HEADER..]) { return Err(LoadError::Checksum) }
+
+    let mut reader = Reader { bytes, pos: HEADER, strings: vec![], numbers: vec![] };
+    for _ in 0..reader.uint()? {
+        let len = reader.uint()?;
+        let x = std::str::from_utf8(reader.take(len)?).map_err(|_| corrupt("string isn't UTF-8"))?;
+        reader.strings.push(x.into());
+    }
+    for _ in 0..reader.uint()? {
+        let x = reader.take(8)?;
+        reader.numbers.push(f64::from_le_bytes(x.try_into().unwrap()));
+    }
+
+    let body = reader.body()?;
+    if reader.pos != bytes.len() { return Err(corrupt("bytes after the program")) }
+    Ok(body)
+}
+
+// FNV-1a
+fn checksum (bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, x| (hash ^ *x as u32).wrapping_mul(0x01000193))
+}
+
+fn uint (bytes: &mut Vec<u8>, mut x: usize) {
+    while x >= 0x80 { bytes.push(x as u8 | 0x80); x >>= 7; }
+    bytes.push(x as u8);
+}
+
+fn corrupt (message: &str) -> LoadError {
+    LoadError::Corrupt(message.to_string())
+}
+
+struct Writer {
+    code: Vec<u8>,
+    strings: Vec<Rc<str>>,
+    string_ids: HashMap<Rc<str>, usize>,
+    numbers: Vec<f64>,
+    // numbers by their bits, so `-0` and `NaN` are kept as they are
+    number_ids: HashMap<u64, usize>
+}
+
+impl Writer {
+
+    fn tag (&mut self, x: u8) {
+        self.code.push(x);
+    }
+
+    fn uint (&mut self, x: usize) {
+        uint(&mut self.code, x);
+    }
+
+    fn string (&mut self, x: &str) {
+        let id = match self.string_ids.get(x) {
+            Some(id) => *id,
+            None => {
+                let x: Rc<str> = x.into();
+                self.strings.push(x.clone());
+                self.string_ids.insert(x, self.strings.len() - 1);
+                self.strings.len() - 1
+            }
+        };
+        self.uint(id);
+    }
+
+    fn number (&mut self, x: f64) {
+        let id = *self.number_ids.entry(x.to_bits()).or_insert_with(|| { self.numbers.push(x); self.numbers.len() - 1 });
+        self.uint(id);
+    }
+
+    fn span (&mut self, span: &Span) {
+        self.string(&span.loc);
+        self.uint(span.line);
+        self.uint(span.col);
+    }
+
+    fn body (&mut self, body: &Body) {
+        self.uint(body.stmts.len());
+        for i in &body.stmts { self.stmt(i); }
+    }
+
+    fn stmt (&mut self, stmt: &Stmt) {
+        let span = &stmt.span;
+        let tag = match &stmt.kind {
+            StmtKind::Expr(_) => 0, StmtKind::Let(..) => 1, StmtKind::Assign(..) => 2, StmtKind::Set(..) => 3, StmtKind::Push(..) => 4,
+            StmtKind::If(..) => 5, StmtKind::Loop(_) => 6, StmtKind::Break => 7, StmtKind::Continue => 8, StmtKind::Block(_) => 9,
+            StmtKind::Println(_) => 10, StmtKind::Fn(..) => 11, StmtKind::Mov(_) => 12, StmtKind::StackOp(_) => 13,
+            StmtKind::CallQuote(..) => 14, StmtKind::ArrayOp(..) => 15, StmtKind::Rust(_) => 16
+        };
+        self.tag(tag);
+        self.span(span);
+        match &stmt.kind {
+            StmtKind::Expr(x) | StmtKind::Println(x) => self.expr(x, span),
+            StmtKind::Let(name, x) | StmtKind::Assign(name, x) => { self.string(name); self.expr(x, span); }
+            StmtKind::Set(arr, index, x) => { self.expr(arr, span); self.expr(index, span); self.expr(x, span); }
+            StmtKind::Push(arr, x) => { self.expr(arr, span); self.expr(x, span); }
+            StmtKind::If(branches, other) => {
+                self.uint(branches.len());
+                for (condition, body) in branches { self.expr(condition, span); self.body(body); }
+                match other {
+                    Some(body) => { self.tag(1); self.body(body); }
+                    None => self.tag(0)
+                }
+            }
+            StmtKind::Loop(body) | StmtKind::Block(body) => self.body(body),
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::Fn(name, args, body) => {
+                self.string(name);
+                self.uint(args.len());
+                for i in args { self.string(i); }
+                self.body(body);
+            }
+            StmtKind::Mov(x) | StmtKind::StackOp(x) => self.string(x),
+            StmtKind::CallQuote(x, args) => { self.expr(x, span); self.exprs(args, span); }
+            StmtKind::ArrayOp(word, args) => { self.string(word); self.exprs(args, span); }
+            StmtKind::Rust(parts) => self.exprs(parts, span)
+        }
+    }
+
+    fn exprs (&mut self, x: &[Expr], span: &Span) {
+        self.uint(x.len());
+        for i in x { self.expr(i, span); }
+    }
+
+    // `span` is the span of the node around the expression
+    fn expr (&mut self, expr: &Expr, span: &Span) {
+        let tag = match &expr.kind {
+            ExprKind::Number(_) => 0, ExprKind::String(_) => 1, ExprKind::Boolean(_) => 2, ExprKind::Undefined => 3, ExprKind::Get(_) => 4,
+            ExprKind::Ref(_) => 5, ExprKind::Binary(..) => 6, ExprKind::Not(_) => 7, ExprKind::Call(..) => 8, ExprKind::Array(_) => 9,
+            ExprKind::Dict(..) => 10, ExprKind::Pick(..) => 11, ExprKind::Type(_) => 12, ExprKind::Builtin(..) => 13, ExprKind::Block(_) => 14,
+            ExprKind::Quote(_) => 15, ExprKind::Curry(..) => 16, ExprKind::Compose(..) => 17, ExprKind::Rust(_) => 18
+        };
+        if expr.span == *span { self.tag(tag); } else { self.tag(tag | SPAN); self.span(&expr.span); }
+        let span = &expr.span;
+        match &expr.kind {
+            ExprKind::Number(x) => self.number(*x),
+            ExprKind::String(x) | ExprKind::Get(x) => self.string(x),
+            ExprKind::Boolean(x) => self.tag(*x as u8),
+            ExprKind::Undefined => {}
+            ExprKind::Ref(x) | ExprKind::Not(x) | ExprKind::Type(x) => self.expr(x, span),
+            ExprKind::Binary(op, a, b) => { self.tag(op.code()); self.expr(a, span); self.expr(b, span); }
+            ExprKind::Pick(a, b) | ExprKind::Curry(a, b) | ExprKind::Compose(a, b) => { self.expr(a, span); self.expr(b, span); }
+            ExprKind::Call(name, args) | ExprKind::Builtin(name, args) => { self.string(name); self.exprs(args, span); }
+            ExprKind::Array(body) | ExprKind::Block(body) | ExprKind::Quote(body) => self.body(body),
+            ExprKind::Dict(k, v) => { self.body(k); self.body(v); }
+            ExprKind::Rust(parts) => self.exprs(parts, span)
+        }
+    }
+
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    strings: Vec<Rc<str>>,
+    numbers: Vec<f64>
+}
+
+type Loaded<T> = Result<T, LoadError>;
+
+impl<'a> Reader<'a> {
+
+    fn take (&mut self, n: usize) -> Loaded<&'a [u8]> {
+        if self.bytes.len() - self.pos < n { return Err(corrupt("unexpected end")) }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn tag (&mut self) -> Loaded<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint (&mut self) -> Loaded<usize> {
+        let mut x = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.tag()?;
+            x |= ((byte & 0x7f) as usize) << shift;
+            if byte < 0x80 { return Ok(x) }
+        }
+        Err(corrupt("integer is too big"))
+    }
+
+    fn rc (&mut self) -> Loaded<Rc<str>> {
+        let id = self.uint()?;
+        self.strings.get(id).cloned().ok_or_else(|| corrupt("string isn't in the pool"))
+    }
+
+    fn string (&mut self) -> Loaded<String> {
+        Ok(self.rc()?.to_string())
+    }
+
+    fn span (&mut self) -> Loaded<Span> {
+        Ok(Span { loc: self.rc()?, line: self.uint()?, col: self.uint()? })
+    }
+
+    fn body (&mut self) -> Loaded<Body> {
+        let n = self.uint()?;
+        let mut stmts = vec![];
+        for _ in 0..n { stmts.push(self.stmt()?); }
+        Ok(Body { stmts })
+    }
+
+    fn stmt (&mut self) -> Loaded<Stmt> {
+        let tag = self.tag()?;
+        let span = self.span()?;
+        let kind = match tag {
+            0 => StmtKind::Expr(self.expr(&span)?),
+            1 => StmtKind::Let(self.string()?, self.expr(&span)?),
+            2 => StmtKind::Assign(self.string()?, self.expr(&span)?),
+            3 => StmtKind::Set(self.expr(&span)?, self.expr(&span)?, self.expr(&span)?),
+            4 => StmtKind::Push(self.expr(&span)?, self.expr(&span)?),
+            5 => {
+                let n = self.uint()?;
+                let mut branches = vec![];
+                for _ in 0..n { branches.push((self.expr(&span)?, self.body()?)); }
+                let other = if self.tag()? == 1 { Some(self.body()?) } else { None };
+                StmtKind::If(branches, other)
+            }
+            6 => StmtKind::Loop(self.body()?),
+            7 => StmtKind::Break,
+            8 => StmtKind::Continue,
+            9 => StmtKind::Block(self.body()?),
+            10 => StmtKind::Println(self.expr(&span)?),
+            11 => {
+                let name = self.string()?;
+                let n = self.uint()?;
+                let mut args = vec![];
+                for _ in 0..n { args.push(self.string()?); }
+                StmtKind::Fn(name, args, self.body()?)
+            }
+            12 => StmtKind::Mov(self.string()?),
+            13 => StmtKind::StackOp(self.string()?),
+            14 => StmtKind::CallQuote(self.expr(&span)?, self.exprs(&span)?),
+            15 => StmtKind::ArrayOp(self.string()?, self.exprs(&span)?),
+            16 => StmtKind::Rust(self.exprs(&span)?),
+            x => return Err(LoadError::Corrupt(format!("unknown statement {x}")))
+        };
+        Ok(Stmt::new(kind, span))
+    }
+
+    fn exprs (&mut self, span: &Span) -> Loaded<Vec<Expr>> {
+        let n = self.uint()?;
+        let mut x = vec![];
+        for _ in 0..n { x.push(self.expr(span)?); }
+        Ok(x)
+    }
+
+    fn boxed (&mut self, span: &Span) -> Loaded<Box<Expr>> {
+        Ok(Box::new(self.expr(span)?))
+    }
+
+    fn expr (&mut self, span: &Span) -> Loaded<Expr> {
+        let tag = self.tag()?;
+        let span = if tag & SPAN != 0 { self.span()? } else { span.clone() };
+        let span = &span;
+        let kind = match tag & !SPAN {
+            0 => {
+                let id = self.uint()?;
+                ExprKind::Number(*self.numbers.get(id).ok_or_else(|| corrupt("number isn't in the pool"))?)
+            }
+            1 => ExprKind::String(self.string()?),
+            2 => ExprKind::Boolean(self.tag()? == 1),
+            3 => ExprKind::Undefined,
+            4 => ExprKind::Get(self.string()?),
+            5 => ExprKind::Ref(self.boxed(span)?),
+            6 => {
+                let op = self.tag()?;
+                let op = BinOp::from_code(op).ok_or_else(|| LoadError::Corrupt(format!("unknown operator {op}")))?;
+                ExprKind::Binary(op, self.boxed(span)?, self.boxed(span)?)
+            }
+            7 => ExprKind::Not(self.boxed(span)?),
+            8 => ExprKind::Call(self.string()?, self.exprs(span)?),
+            9 => ExprKind::Array(self.body()?),
+            10 => ExprKind::Dict(self.body()?, self.body()?),
+            11 => ExprKind::Pick(self.boxed(span)?, self.boxed(span)?),
+            12 => ExprKind::Type(self.boxed(span)?),
+            13 => ExprKind::Builtin(self.string()?, self.exprs(span)?),
+            14 => ExprKind::Block(self.body()?),
+            15 => ExprKind::Quote(self.body()?),
+            16 => ExprKind::Curry(self.boxed(span)?, self.boxed(span)?),
+            17 => ExprKind::Compose(self.boxed(span)?, self.boxed(span)?),
+            18 => ExprKind::Rust(self.exprs(span)?),
+            x => return Err(LoadError::Corrupt(format!("unknown expression {x}")))
+        };
+        Ok(Expr::new(kind, span.clone()))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::{read_dir, read_to_string}, path::Path};
+    use crate::lexer::lex;
+    use crate::bytecode::{binds::Binds, gen::get_statements, text::parse};
+    use super::{checksum, decode, encode, LoadError, HEADER, VERSION};
+
+    #[test]
+    fn examples_are_loaded_as_they_were_written () {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        for path in read_dir(root.join("examples")).unwrap().map(|x| x.unwrap().path()) {
+            let ir = get_statements(&lex(read_to_string(&path).unwrap(), vec![]), &mut Binds::new()).unwrap();
+            assert_eq!(decode(&encode(&ir)), Ok(ir), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn spans_and_numbers_are_kept () {
+        let ir = parse(r#"1:1     let x = [-0, 0.5, 0.5, "a", "a"]
+# lib.alt
+2:20    if !(&x)[0] {
+3:1         println sum!(@1:1 x, rust!("1", x))
+        } else if @2:30 (x != @"other.alt":7:7 2) {
+3:5         block {
+3:9             stack dup
+            }
+        }
+"#).unwrap();
+        assert_eq!(decode(&encode(&ir)), Ok(ir));
+    }
+
+    #[test]
+    fn damaged_modules_are_rejected () {
+        let bytes = encode(&parse("1:1     println (1 + 2)\n").unwrap());
+        assert_eq!(decode(b"fn main () {}"), Err(LoadError::NotModule));
+
+        let mut other = bytes.clone();
+        other[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(decode(&other), Err(LoadError::Version(VERSION + 1)));
+
+        for i in HEADER..bytes.len() {
+            let mut damaged = bytes.clone();
+            damaged[i] ^= 0x10;
+            assert_eq!(decode(&damaged), Err(LoadError::Checksum), "byte {i}");
+        }
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), Err(LoadError::Checksum));
+
+        // damaged before the checksum was computed
+        let mut cut = bytes[..bytes.len() - 1].to_vec();
+        let sum = checksum(&cut[HEADER..]);
+        cut[6..HEADER].copy_from_slice(&sum.to_le_bytes());
+        assert_eq!(decode(&cut), Err(LoadError::Corrupt("unexpected end".to_string())));
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use crate::lexer::Token;
+use crate::diagnostic::Diagnostic;
+use super::{item::Item, value::Value};
+
+// what a name means to the generator
+#[derive(Clone, Debug, PartialEq)]
+pub enum Bind {
+    // variable with its name in the IR, which differs for `index`, and its value when it's known at compile time
+    Var(String, Option<Value>),
+    // function with amount of its arguments
+    Fn(usize),
+    // tokens of macro, and whether it takes a body after it (`macro:b:`)
+    Macro(Vec<Token>, bool),
+    // parse modes of the scope: `fn`, `quote`, `loop` or `array`
+    Modes(HashSet<String>),
+    // item pushed for the name: values of `let`, index of unrolled `times`, references of function arguments
+    Item(Box<Item>)
+}
+
+// kind of scope, it decides what is visible from outer scopes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    // body of if-statement, loop, `let`, block or array: everything is visible
+    Block,
+    // variables of outer scopes can't be used
+    Function,
+    // quotation runs later, so values of outer variables aren't known
+    Quote
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    kind: Scope,
+    // names with their values and time of binding
+    names: HashMap<String, (Bind, usize)>,
+    // `Binds::forgotten` when the scope was entered
+    forgotten: usize,
+    // time of the latest known value of a variable bound in the scope
+    known: Option<usize>,
+    // nested scope made a variable of an outer scope unknown
+    changed: bool
+}
+
+/* Symbol table of the generator. Nested body gets its own scope, which shadows outer ones and is dropped after the body,
+so entering a body doesn't copy anything. Variables with values known at compile time are bound with their values,
+which are dropped when they become unknown. */
+#[derive(Clone, Debug)]
+pub struct Binds {
+    frames: Vec<Frame>,
+    time: usize,
+    // known values bound before this time are forgotten
+    forgotten: usize,
+    // warnings about the code, reported by the caller with its source
+    warnings: Vec<Diagnostic>,
+    // errors of invalid quotations which became runtime code, reported with the body they are in
+    invalid: Vec<Diagnostic>
+}
+
+impl Default for Binds {
+    fn default () -> Self {
+        Binds { frames: vec![Frame { kind: Scope::Block, names: HashMap::new(), forgotten: 0, known: None, changed: false }], time: 0, forgotten: 0, warnings: vec![], invalid: vec![] }
+    }
+}
+
+impl Binds {
+
+    pub fn new () -> Self {
+        Self::default()
+    }
+
+    pub fn get (&self, name: &str) -> Option<Bind> {
+        let (mut hidden, mut unknown) = (false, false);
+        for frame in self.frames.iter().rev() {
+            if let Some((x, time)) = frame.names.get(name) {
+                return match x {
+                    Bind::Var(..) if hidden => None,
+                    Bind::Var(x, Some(_)) if unknown || *time < self.forgotten => Some(Bind::Var(x.clone(), None)),
+                    x => Some(x.clone())
+                }
+            }
+            match frame.kind {
+                Scope::Function => hidden = true,
+                Scope::Quote => unknown = true,
+                Scope::Block => {}
+            }
+        }
+        None
+    }
+
+    pub fn contains_key (&self, name: &str) -> bool {
+        self.frames.iter().any(|x| x.names.contains_key(name))
+    }
+
+    // names of variables, known or not
+    pub fn variables (&self) -> Vec<String> {
+        self.frames.iter().flat_map(|x| &x.names).filter(|(_, (x, _))| matches!(x, Bind::Var(..))).map(|(k, _)| k.clone()).collect()
+    }
+
+    // binds name in the current scope
+    pub fn insert (&mut self, name: String, value: Bind) {
+        let frame = self.frames.last_mut().unwrap();
+        if let Bind::Var(_, Some(_)) = value { frame.known = Some(self.time); }
+        frame.names.insert(name, (value, self.time));
+        self.time += 1;
+    }
+
+    pub fn remove (&mut self, name: &str) {
+        self.frames.last_mut().unwrap().names.remove(name);
+    }
+
+    // value of the variable isn't known anymore
+    pub fn forget (&mut self, name: &str) {
+        if let Some(Bind::Var(x, Some(_))) = self.get(name) { self.insert(name.to_string(), Bind::Var(x, None)); }
+    }
+
+    // code executed at runtime (quotations, functions getting them, rust code) can change any variable
+    pub fn forget_all (&mut self) {
+        self.forgotten = self.time;
+    }
+
+    pub fn warn (&mut self, warnings: Vec<Diagnostic>) {
+        self.warnings.extend(warnings);
+    }
+
+    // warnings reported since the last call
+    pub fn take_warnings (&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn invalid (&mut self, errors: Vec<Diagnostic>) {
+        self.invalid.extend(errors);
+    }
+
+    // errors of invalid quotations since the last call
+    pub fn take_invalid (&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.invalid)
+    }
+
+    // runs `f` in a new scope. returns its result and whether known variables of outer scopes were forgotten in it
+    pub fn scoped<T> (&mut self, kind: Scope, f: impl FnOnce(&mut Binds) -> T) -> (T, bool) {
+        self.frames.push(Frame { kind, names: HashMap::new(), forgotten: self.forgotten, known: None, changed: false });
+        let result = f(self);
+        let changed = self.pop();
+        (result, changed)
+    }
+
+    fn pop (&mut self) -> bool {
+        let frame = self.frames.pop().unwrap();
+        // function and quotation don't run here, so they don't change variables around them
+        if frame.kind != Scope::Block { self.forgotten = frame.forgotten; return false }
+
+        let mut changed = frame.changed || self.forgotten > frame.forgotten && self.frames.iter().any(|x| x.known.is_some_and(|t| t >= frame.forgotten));
+        // variables changed by nested code aren't known after it, as it may not run or run several times
+        for (name, (x, _)) in frame.names {
+            if !matches!(x, Bind::Var(..)) { continue }
+            let Some(Bind::Var(_, Some(_))) = self.get(&name) else { continue };
+            let Some(owner) = self.frames.iter().rposition(|f| f.names.contains_key(&name)) else { continue };
+            let outer = &mut self.frames[owner].names.get_mut(&name).unwrap().0;
+            if *outer == x { continue }
+            if let Bind::Var(_, known) = outer { *known = None; }
+            changed = true;
+            // scopes between this one and the owner of the variable are changed too, like loop around if-statement
+            for f in &mut self.frames[owner + 1..] { f.changed = true; }
+        }
+        changed
+    }
+
+}
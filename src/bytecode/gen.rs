@@ -1,21 +1,27 @@
-use std::{collections::{HashMap, HashSet}, fs::File, io::Read, path::absolute};
+use std::{cell::Cell, collections::HashSet, fs::File, io::Read, path::absolute};
 
-use crate::lexer::{lex, Token, TokenKind};
-use crate::lexer_rules::get_lexer_rules;
+use crate::lexer::{lex, lex_error, lex_errors, Token, TokenKind};
+use crate::diagnostic::{Diagnostic, Severity};
 
 use super::parse::*;
 
-use crate::bytecode::value::Value;
+use crate::bytecode::value::{Value, stack_effect, values};
+use crate::bytecode::binds::{Bind, Binds, Scope};
+use crate::bytecode::item::Item;
+use crate::bytecode::{ir::{BinOp, Body, Expr, ExprKind, Span, Stmt, StmtKind}, ops::{binary, pick, to_runtime}, scope::declare};
 
 macro_rules! bc_error {
     ($token:expr, $msg:expr) => {
-        panic!("{}:{} {}", $token.line, $token.col, $msg);
+        return Err(Diagnostic::error($token, $msg).into())
+    };
+    ($token:expr, $msg:expr, $help:expr) => {
+        return Err(Diagnostic::error($token, $msg).help($help).into())
     };
 }
 
 macro_rules! get_mode {
     ($binds:expr, $y:tt, $($x:expr),+) => {
-        if let Some(Value::ParseModes(v)) = $binds.get("=") {
+        if let Some(Bind::Modes(v)) = $binds.get("=") {
             $(
                 if let Some(_) = v.get($x) { $y }
             )+
@@ -24,359 +30,1131 @@ macro_rules! get_mode {
 }
 macro_rules! add_mode {
     ($binds:expr, $($x:expr),+) => {
-        if let Some(Value::ParseModes(v)) = $binds.get("=") {
+        if let Some(Bind::Modes(v)) = $binds.get("=") {
             let mut v = v.clone(); $(v.insert($x.to_string()))+;
-            $binds.insert("=".to_string(), Value::ParseModes(v));
+            $binds.insert("=".to_string(), Bind::Modes(v));
         }
-        else { $binds.insert("=".to_string(), Value::ParseModes(HashSet::from([$($x.to_string(),)+]))); }
+        else { $binds.insert("=".to_string(), Bind::Modes(HashSet::from([$($x.to_string(),)+]))); }
     };
 }
 
-pub fn get_all_instructions (tokens: Vec<Token>, instructions: &mut Vec<Value>, binds: &mut HashMap<String, Value>) -> Vec<Value> {
+// when this key is in binds, unrecognized characters are reported as warnings instead of errors
+pub const LEX_WARNINGS: &str = "*LEX_WARNINGS";
+// biggest count of `times` which is unrolled at compile time, `DEFAULT_UNROLL_LIMIT` when this key isn't in binds
+pub const UNROLL_LIMIT: &str = "*UNROLL_LIMIT";
+pub const DEFAULT_UNROLL_LIMIT: usize = 256;
+// number of iteration inside of `times`
+const INDEX: &str = "index";
+
+// diagnostics of failed instruction and amount of tokens to skip before generation continues
+#[derive(Debug)]
+pub struct Failure {
+    pub diagnostics: Vec<Diagnostic>,
+    pub skip: usize
+}
+
+impl From<Diagnostic> for Failure {
+    fn from (diagnostic: Diagnostic) -> Self {
+        Failure { diagnostics: vec![diagnostic], skip: 1 }
+    }
+}
+
+// use it with `map_err` when nested body fails, so whole instruction is skipped
+fn skip (len: usize) -> impl FnOnce(Vec<Diagnostic>) -> Failure {
+    move |diagnostics| Failure { diagnostics, skip: len }
+}
+
+pub fn get_all_instructions (tokens: &[Token], instructions: &mut Vec<Item>, binds: &mut Binds) -> Result<(), Vec<Diagnostic>> {
+    let (_, errors) = generate(&Tokens::new(tokens), tokens.len(), instructions, binds, &mut vec![]);
+    if !errors.is_empty() { return Err(errors) }
+    Ok(())
+}
+
+// generates the program as IR
+pub fn get_statements (tokens: &[Token], binds: &mut Binds) -> Result<Body, Vec<Diagnostic>> {
+    // temporary variables are numbered for each program, so its IR doesn't depend on what was generated before
+    TEMPS.set(0);
+    get_statements_on(false, tokens, binds)
+}
+
+// same as `get_statements`, but temporary variables continue the numbering and statements can pop values pushed
+// before them from the runtime stack, like quotations do, when `on_stack` is true
+pub fn get_statements_on (on_stack: bool, tokens: &[Token], binds: &mut Binds) -> Result<Body, Vec<Diagnostic>> {
+    let declared = binds.variables();
+    let stack = if on_stack { vec![Item::Pops(None, vec![])] } else { vec![] };
+    let mut body = statements_on(stack, tokens, binds)?;
+    declare(&mut body, declared);
+    Ok(body)
+}
+
+// generates body of statements with spans of their tokens, so generated code can be mapped back to the source
+fn statements (tokens: &[Token], binds: &mut Binds) -> Result<Body, Vec<Diagnostic>> {
+    statements_on(vec![], tokens, binds)
+}
+
+fn statements_on (stack: Vec<Item>, tokens: &[Token], binds: &mut Binds) -> Result<Body, Vec<Diagnostic>> {
+    let instructions = instructions_on(stack, tokens, binds)?;
+    Ok(body_of(instructions, binds))
+}
+
+// generates instructions of a body with spans of the tokens where they start
+fn instructions_on (stack: Vec<Item>, tokens: &[Token], binds: &mut Binds) -> Result<Vec<(Item, Option<Span>)>, Vec<Diagnostic>> {
+    let around = binds.take_invalid();
+    let mut starts = vec![0; stack.len()];
+    let mut instructions = stack;
+    let (_, errors) = generate(&Tokens::new(tokens), tokens.len(), &mut instructions, binds, &mut starts);
+    let invalid = binds.take_invalid();
+    binds.invalid(around);
+    if !errors.is_empty() { return Err(errors) }
+
+    let errors: Vec<Diagnostic> = invalid.into_iter().chain(quotation_errors(&instructions)).collect();
+    if !errors.is_empty() { return Err(errors) }
+
+    let mut last: Option<Span> = None;
+    Ok(instructions.into_iter().zip(starts).map(|(x, start)| {
+        let span = tokens.get(start).map(|t| {
+            let loc = match &last { Some(x) if *x.loc == *t.loc => x.loc.clone(), _ => t.loc.as_str().into() };
+            Span { loc, line: t.line, col: t.col }
+        });
+        if span.is_some() { last = span.clone(); }
+        (x, span)
+    }).collect())
+}
+
+// generates instructions starting before `end`, the last one can take tokens after it. returns amount of taken tokens.
+// `starts` gets index of the token where each instruction starts
+fn generate (tokens: &Tokens, end: usize, instructions: &mut Vec<Item>, binds: &mut Binds, starts: &mut Vec<usize>) -> (usize, Vec<Diagnostic>) {
+    let mut errors: Vec<Diagnostic> = vec![];
     let mut token = 0;
-    while token < tokens.len() {
-        token += get_instruction(&tokens[token..].to_vec(), instructions, binds);
+    while token < end {
+        let result = get_instruction(&tokens.skip(token), instructions, binds);
+        starts.resize(instructions.len(), token);
+        match result {
+            Ok(x) => token += x,
+            Err(mut x) => { errors.append(&mut x.diagnostics); token += x.skip.max(1); }
+        }
+    }
+
+    errors.dedup_by(|a, b| a.span == b.span && a.message == b.message);
+    (token, errors)
+}
+
+// errors of quotations which are left for the runtime. quotations expanded at compile time can be invalid on their own,
+// errors of the ones which became expressions are reported by `expr`
+fn quotation_errors<'a> (instructions: impl IntoIterator<Item = &'a Item>) -> Vec<Diagnostic> {
+    instructions.into_iter().flat_map(|x| match x {
+        Item::Quote(_, Err(e)) => e.clone(),
+        Item::Array(x) | Item::Block(x) => quotation_errors(x),
+        _ => vec![]
+    }).collect()
+}
+
+// generates body of `do {...}` as code executed on the runtime stack: values it can't find are popped from it
+fn quotation (tokens: &[Token], binds: &mut Binds) -> Result<Body, Vec<Diagnostic>> {
+    // quotation runs later, when variables can have other values
+    binds.scoped(Scope::Quote, |binds| {
+        binds.insert("=".to_string(), Bind::Modes(HashSet::from(["quote".to_string()])));
+        statements_on(vec![Item::Pops(None, vec![])], tokens, binds)
+    }).0
+}
+
+thread_local! {
+    // amount of temporary variables of the program
+    static TEMPS: Cell<usize> = const { Cell::new(0) };
+}
+
+// name of temporary variable, which can't be written in alt code
+fn temp (prefix: &str) -> String {
+    let i = TEMPS.replace(TEMPS.get() + 1);
+    format!("{prefix}{i}_").replace('-', "_")
+}
+
+// statements of instructions, with spans of the tokens where they start when they are known
+fn body_of (instructions: Vec<(Item, Option<Span>)>, binds: &mut Binds) -> Body {
+    let mut stmts: Vec<Stmt> = vec![];
+    for (x, span) in instructions {
+        let at = stmts.len();
+        match x {
+            // condition of `else if` keeps its own span
+            Item::ElseIf(mut x) => {
+                if let Some(span) = &span { place(&mut x, span); }
+                if let (Some(StmtKind::If(branches, other @ None)), StmtKind::If(more, rest)) = (stmts.last_mut().map(|x| &mut x.kind), &mut x.kind) {
+                    branches.append(more);
+                    *other = rest.take();
+                    continue
+                }
+                // `else if` after if-statement failed at compile time starts new if-statement
+                stmts.push(x);
+            }
+            x => stmts_of(x, binds, &mut stmts)
+        }
+        if let Some(span) = &span { stmts[at..].iter_mut().for_each(|x| place(x, span)); }
+    }
+    Body { stmts }
+}
+
+fn body (instructions: Vec<Item>, binds: &mut Binds) -> Body {
+    body_of(instructions.into_iter().map(|x| (x, None)).collect(), binds)
+}
+
+fn stmts_of (x: Item, binds: &mut Binds, stmts: &mut Vec<Stmt>) {
+    let kind = match x {
+        Item::Stmt(x) | Item::ElseIf(x) => { stmts.push(x); return }
+        Item::Block(x) => StmtKind::Block(body(x, binds)),
+        Item::Pops(x, vars) => {
+            stmts.extend(x);
+            stmts.extend(vars.into_iter().map(|x| Stmt::new(StmtKind::Mov(x), Span::default())));
+            return
+        }
+        Item::PassedIf | Item::FailedIf => return,
+        x => StmtKind::Expr(expr(x, binds))
+    };
+    stmts.push(Stmt::new(kind, Span::default()));
+}
+
+// expression of a value of the compile-time stack. its span is given by the statement which takes it
+fn expr (x: Item, binds: &mut Binds) -> Expr {
+    let kind = match x {
+        Item::Const(x) => return x.expr(),
+        Item::Expr(x) => return x,
+        Item::Quote(_, body) => ExprKind::Quote(body.unwrap_or_else(|e| { binds.invalid(e); Body::default() })),
+        Item::Array(x) => ExprKind::Array(body(x, binds)),
+        Item::Block(x) => ExprKind::Block(body(x, binds)),
+        // statement taken by `let` with `:name`
+        _ => ExprKind::Undefined
+    };
+    Expr::new(kind, Span::default())
+}
+
+fn exprs (x: Vec<Item>, binds: &mut Binds) -> Vec<Expr> {
+    x.into_iter().map(|x| expr(x, binds)).collect()
+}
+
+// gives span of the statement to its nodes generated without one, like the values it takes. lines start at 1
+fn place (x: &mut Stmt, span: &Span) {
+    if x.span.line != 0 { return }
+    x.span = span.clone();
+    let (exprs, bodies) = x.children_mut();
+    exprs.into_iter().for_each(|x| place_expr(x, span));
+    bodies.into_iter().flat_map(|x| &mut x.stmts).for_each(|x| place(x, span));
+}
+
+fn place_expr (x: &mut Expr, span: &Span) {
+    if x.span.line != 0 { return }
+    x.span = span.clone();
+    let (exprs, bodies) = x.children_mut();
+    exprs.into_iter().for_each(|x| place_expr(x, span));
+    bodies.into_iter().flat_map(|x| &mut x.stmts).for_each(|x| place(x, span));
+}
+
+// reports characters that lexer could not recognize in lexed code
+fn check_lexed (tokens: &[Token], binds: &mut Binds) -> Result<(), Failure> {
+    if binds.contains_key(LEX_WARNINGS) {
+        binds.warn(lex_errors(tokens, Severity::Warning));
+        return Ok(())
     }
-    
-    instructions.to_vec()
+
+    let errors = lex_errors(tokens, Severity::Error);
+    if !errors.is_empty() { return Err(Failure { diagnostics: errors, skip: 1 }) }
+    Ok(())
+}
+
+// pops value for the operation of `token`, reporting stack underflow
+fn pop_operand (instructions: &mut Vec<Item>, token: &Token, what: &str) -> Result<Item, Diagnostic> {
+    popv(instructions).ok_or_else(|| Diagnostic::error(token, format!("stack underflow: `{}` expects {what}", token.value))
+        .help(format!("push {what} before `{}`", token.value)))
 }
 
-fn get_instruction (tokens: &Vec<Token>, instructions: &mut Vec<Value>, binds: &mut HashMap<String, Value>) -> usize {
+fn get_instruction (tokens: &Tokens, instructions: &mut Vec<Item>, binds: &mut Binds) -> Result<usize, Failure> {
     let token = &tokens[0];
 
     match token.typ {
-        TokenKind::String => {instructions.push(Value::String(parse_string(token.value.clone()))); return 1}
-        TokenKind::Int => {instructions.push(Value::Number(format!("{}.0", token.value).parse::<f64>().unwrap())); return 1}
-        TokenKind::Real => {instructions.push(Value::Number(token.value.parse::<f64>().unwrap())); return 1}
+        TokenKind::String => {
+            // string is pushed anyway, so code after it doesn't report stack underflow
+            let x = parse_string(token);
+            instructions.push(Item::Const(Value::String(x.clone().unwrap_or_default())));
+            x?;
+            Ok(1)
+        }
+        TokenKind::Int | TokenKind::Real => {
+            let x = parse_number(token);
+            instructions.push(Item::Const(Value::Number(x.clone().unwrap_or_default())));
+            x?;
+            Ok(1)
+        }
         TokenKind::Operator | TokenKind::Logical => {
             let v = token.value.as_str();
-            let b = Box::new(popv(instructions).expect(&format!("{}:{} op failed 1", token.line, token.col)));
-            if v == "not" || v == "!" { instructions.push(Value::Not(b).process()); return 1 }
-            let a = Box::new(popv(instructions).expect(&format!("{}:{} op failed 2", token.line, token.col)));
-            match v {
-                "+" | "-" | "/" | "*" | "%" | "<<" | ">>" => instructions.push(Value::NumOp(a, b, token.value.clone()).process()),
-                "**" => instructions.push(Value::Pow(a, b)),
-                "=" | "!=" | "&&" | "||" | "<" | ">" | "<=" | ">=" => instructions.push(Value::LogOp(a, b, token.value.clone()).process()),
-                "and" => instructions.push(Value::LogOp(a, b, "&&".to_string())),
-                "or" => instructions.push(Value::LogOp(a, b, "||".to_string())),
-                _ => {}
+            let b = pop_operand(instructions, token, "an operand")?;
+            if v == "not" || v == "!" {
+                let x = match known(b.clone(), binds).constant().and_then(|x| x.not_static()) {
+                    Some(x) => Item::Const(x),
+                    None => Item::expr(ExprKind::Not(Box::new(expr(b, binds))))
+                };
+                instructions.push(x);
+                return Ok(1)
             }
-            return 1
+            let a = pop_operand(instructions, token, "two operands")?;
+            let op = match v {
+                "and" => BinOp::And,
+                "or" => BinOp::Or,
+                v => match BinOp::parse(v) { Some(x) => x, None => return Ok(1) }
+            };
+            if matches!(op, BinOp::Div | BinOp::Rem) && divides_by_zero(&known(a.clone(), binds), &known(b.clone(), binds)) {
+                // result is pushed anyway, so code after it doesn't report stack underflow
+                instructions.push(binary_expr(a, b, op, binds));
+                return Err(Diagnostic::error(token, "division by zero").note("both operands are known at compile time").into())
+            }
+            // `and` and `or` aren't folded
+            let x = if v == "and" || v == "or" { binary_expr(a, b, op, binds) } else { fold_binary(a, b, op, binds) };
+            instructions.push(x);
+            Ok(1)
         }
         TokenKind::Assign => {
-            if let TokenKind::Keyword = tokens[1].typ {
-                let a = popv(instructions).expect(&format!("{}:{} cannot get value to assign to variable.", token.line, token.col));
-                if let Some(Value::Ref(_)) = binds.get(tokens[1].value.as_str()) {
-                    instructions.push(Value::RefAssign(tokens[1].value.clone(), Box::new(a)));
-                    return 2
-                }
-                binds.insert(tokens[1].value.clone(), Value::Get(tokens[1].value.clone()));
-                instructions.push(Value::Var(tokens[1].value.clone(), Box::new(a)));
+            match tokens.get(1) {
+                Some(name) if name.typ == TokenKind::Keyword => {
+                    let a = pop_operand(instructions, token, "a value to assign")?;
+                    // argument of function is changed through its reference
+                    let reference = matches!(binds.get(name.value.as_str()), Some(Bind::Item(x)) if matches!(*x, Item::Expr(Expr { kind: ExprKind::Ref(_), .. })));
+                    if !reference {
+                        // value known at compile time is remembered, so operations with the variable are folded
+                        let value = match known(a.clone(), binds) {
+                            x if !matches!(x, Item::Block(_)) => x.constant(),
+                            _ => None
+                        };
+                        binds.insert(name.value.clone(), Bind::Var(name.value.clone(), value));
+                    }
+                    let a = expr(a, binds);
+                    instructions.push(Item::stmt(StmtKind::Let(name.value.clone(), a)));
+                }
+                Some(name) => { bc_error!(name, format!("variable name must be a keyword, found `{}`", name.value)); }
+                None => { bc_error!(token, format!("expected variable name after `{}`", token.value)); }
             }
-            else { bc_error!(token, "Variable name must be a keyword"); }
 
-            return 2
+            Ok(2)
         }
         TokenKind::SpecialSymbol => {
-            match token.value.as_str() {
-                "&" => {
-                    let a = popv(instructions).unwrap();
-                    instructions.push(Value::Ref(Box::new(a)));
-                }
-                _ => {}
+            if token.value == "&" {
+                let a = pop_operand(instructions, token, "a value to reference")?;
+                // variable can be changed by the reference
+                if let Some(name) = variable(&a) { binds.forget(name); }
+                let a = expr(a, binds);
+                instructions.push(Item::expr(ExprKind::Ref(Box::new(a))));
             }
-            return 1
+            Ok(1)
         }
         TokenKind::Keyword => {
             match token.value.as_str() {
-                "true" | "false" => { instructions.push(Value::Boolean(token.value == "true")); return 1 }
+                "true" | "false" => { instructions.push(Item::Const(Value::Boolean(token.value == "true"))); return Ok(1) }
+                "inf" => { instructions.push(Item::Const(Value::Number(f64::INFINITY))); return Ok(1) }
+                "nan" => { instructions.push(Item::Const(Value::Number(f64::NAN))); return Ok(1) }
                 "if" => {
-                    match parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")) {
-                        Some(x) => {
-                            let condition = instructions.pop().expect(&format!("{}:{} cannot parse condition for if-statement", token.line, token.col));
-                            if let Value::Boolean(false) = condition { instructions.push(Value::FailedIf); return x.1 }
-                            let body = get_all_instructions(x.0, &mut vec![], &mut binds.clone());
-                            if let Value::Boolean(true) = condition { instructions.push(Value::Block(body)); instructions.push(Value::PassedIf); return x.1 }
-                            instructions.push(Value::If(Box::new(condition), body));
-
-                            return x.1
-                        }
-                        None => {}
-                    }
+                    let x = parse_body(tokens, 1, ("{", "}"))?;
+                    let Some(condition) = popv(instructions) else { bc_error!(token, "cannot parse condition for if-statement", "push condition before `if`"); };
+                    let condition = fold(condition, binds);
+                    if let Item::Const(Value::Boolean(false)) = condition { instructions.push(Item::FailedIf); return Ok(x.1) }
+                    let body = scoped(&x.0, binds).map_err(skip(x.1))?;
+                    // body of statically passed if-statement becomes a block, which can be used as a value
+                    if let Item::Const(Value::Boolean(true)) = condition { instructions.push(Item::Block(without_spans(body))); instructions.push(Item::PassedIf); return Ok(x.1) }
+                    let branch = (expr(condition, binds), body_of(body, binds));
+                    instructions.push(Item::stmt(StmtKind::If(vec![branch], None)));
+
+                    return Ok(x.1)
                 }
                 "else" => {
-                    match parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")) {
-                        Some(x) => {
-                            let previous_ins = instructions.last().unwrap();
-                            if let Value::PassedIf = previous_ins { instructions.pop(); return x.1 }
-                            let body = get_all_instructions(x.0, &mut vec![], &mut binds.clone());
-                            if let Value::FailedIf = previous_ins { instructions.pop(); instructions.push(Value::Block(body)); return x.1 }
-                            instructions.push(Value::Else(body));
-                            return x.1
-                        }
-                        None => {
-                            if tokens[1].value == "if".to_string() {
-                                let c = instructions.pop().unwrap();
-                                let previous_ins = instructions.last().unwrap();
-                                let b = parse_pair_symbols(&tokens[2..].to_vec(), ("{", "}")).unwrap();
-                                if let Value::PassedIf = previous_ins { return b.1 + 1 }
-                                let body = get_all_instructions(b.0, &mut vec![], &mut binds.clone());
-                                if let Value::FailedIf = previous_ins {
-                                    if let Value::Boolean(true) = c { instructions.pop(); instructions.push(Value::Block(body)); instructions.push(Value::PassedIf); return b.1 + 1 }
-                                    else if let Value::Boolean(false) = c { return b.1 + 1 }
-                                }
-                                instructions.push(Value::ElseIf(Box::new(c), body));
-                                return b.1 + 1
-                            }
+                    if tokens.get(1).is_some_and(|x| x.value == "if") {
+                        let b = parse_body(tokens, 2, ("{", "}"))?;
+                        let Some(c) = popv(instructions) else { bc_error!(token, "cannot parse condition for else-if-statement", "push condition before `else if`"); };
+                        let c = fold(c, binds);
+                        let Some(previous_ins) = instructions.last().filter(|x| is_open(x)) else { bc_error!(token, "`else if` without preceding `if`"); };
+                        if let Item::PassedIf = previous_ins { return Ok(b.1) }
+                        let failed = matches!(previous_ins, Item::FailedIf);
+                        let body = scoped(&b.0, binds).map_err(skip(b.1))?;
+                        if failed {
+                            if let Item::Const(Value::Boolean(true)) = c { instructions.pop(); instructions.push(Item::Block(without_spans(body))); instructions.push(Item::PassedIf); return Ok(b.1) }
+                            else if let Item::Const(Value::Boolean(false)) = c { return Ok(b.1) }
                         }
+                        let branch = (expr(c, binds), body_of(body, binds));
+                        let x = Stmt::new(StmtKind::If(vec![branch], None), Span::default());
+                        instructions.push(if failed { Item::Stmt(x) } else { Item::ElseIf(x) });
+                        return Ok(b.1)
                     }
+
+                    let x = parse_body(tokens, 1, ("{", "}"))?;
+                    let Some(previous_ins) = instructions.last().filter(|x| is_open(x)) else { bc_error!(token, "`else` without preceding `if`"); };
+                    if let Item::PassedIf = previous_ins { instructions.pop(); return Ok(x.1) }
+                    let failed = matches!(previous_ins, Item::FailedIf);
+                    let body = scoped(&x.0, binds).map_err(skip(x.1))?;
+                    if failed { instructions.pop(); instructions.push(Item::Block(without_spans(body))); return Ok(x.1) }
+                    let body = body_of(body, binds);
+                    if let Some(Item::Stmt(Stmt { kind: StmtKind::If(_, other), .. }) | Item::ElseIf(Stmt { kind: StmtKind::If(_, other), .. })) = instructions.last_mut() { *other = Some(body); }
+                    return Ok(x.1)
                 }
                 "loop" => {
-                    match parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")) {
-                        Some(b) => {
-                            let binds = &mut binds.clone();
-                            add_mode!(binds, "loop");
-                            instructions.push(Value::Loop(get_all_instructions(b.0, &mut vec![], binds)));
-                            return b.1
-                        }
-                        None => {}
-                    }
+                    let b = parse_body(tokens, 1, ("{", "}"))?;
+                    instructions.push(Item::stmt(StmtKind::Loop(loop_body(&b.0, binds, vec![]).map_err(skip(b.1))?)));
+                    return Ok(b.1)
                 }
-                "break" => { get_mode!(binds, { instructions.push(Value::Break); return 1; }, "loop"); bc_error!(token, "`break` can be used only in loops"); }
-                "continue" => { get_mode!(binds, { instructions.push(Value::Continue); return 1; }, "loop"); bc_error!(token, "`continue` can be used only in loops"); }
+                "break" => { get_mode!(binds, { instructions.push(Item::stmt(StmtKind::Break)); return Ok(1); }, "loop"); bc_error!(token, "`break` can be used only in loops"); }
+                "continue" => { get_mode!(binds, { instructions.push(Item::stmt(StmtKind::Continue)); return Ok(1); }, "loop"); bc_error!(token, "`continue` can be used only in loops"); }
                 "let" => {
                     let (mut args, last) = parse_args(tokens, 1);
-                    match parse_pair_symbols(&tokens[last..].to_vec(), ("{", "}")) {
-                        Some(body) => {
-                            let mut binds = binds.clone();
-                            let l = args.len();
-                            args.reverse();
-                            args.iter().for_each(|i| {
-                                binds.insert(i.clone(), {
-                                    if i.clone().starts_with(":") { instructions.pop().expect("Cannot get instruction in `let`") } else { popv(instructions).expect("Cannot pop value in `let`") }
-                                    // TODO: better errors
-                                });
-                            });
-                            get_all_instructions(body.0, instructions, &mut binds);
-                            return body.1 + l
-                        }
-                        None => {}
+                    let body = parse_body(tokens, last, ("{", "}"))?;
+                    let l = args.len();
+                    args.reverse();
+                    let mut values = vec![];
+                    for i in args {
+                        let value = if i.starts_with(':') { instructions.pop() } else { popv(instructions) };
+                        let Some(value) = value else {
+                            return Err(Diagnostic::error(token, format!("stack underflow: `let` expects {l} values"))
+                                .note(format!("cannot get value for `{i}`")).into())
+                        };
+                        values.push((i, value));
                     }
+                    binds.scoped(Scope::Block, |binds| {
+                        values.into_iter().for_each(|(name, value)| binds.insert(name, Bind::Item(Box::new(value))));
+                        get_all_instructions(&body.0, instructions, binds)
+                    }).0.map_err(skip(body.1))?;
+                    return Ok(body.1)
                 }
                 "times" => {
-                    let times = popv(instructions).unwrap();
-                    if let Value::Number(a) = times {
-                        let body = parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")).unwrap();
+                    let times = pop_operand(instructions, token, "a count")?;
+                    let b = parse_body(tokens, 1, ("{", "}"))?;
+                    let limit = match binds.get(UNROLL_LIMIT) {
+                        Some(Bind::Item(x)) => match *x { Item::Const(Value::Number(x)) => x as usize, _ => DEFAULT_UNROLL_LIMIT },
+                        _ => DEFAULT_UNROLL_LIMIT
+                    };
+                    // count is used as integer, like `int` does
+                    let count = known(times.clone(), binds).constant().and_then(|x| x.word_static("int"));
+                    // `break` and `continue` need the loop, unrolled body would leave the loop around it
+                    let unrolled = count.clone().filter(|x| matches!(x, Value::Number(a) if *a <= limit as f64) && !jumps(&b.0, binds, &mut HashSet::new()));
+                    if let Some(Value::Number(a)) = unrolled {
+                        let (index, modes) = (binds.get(INDEX), binds.get("="));
+                        // jumps which come from elsewhere, like tokens added to quotation, are errors
+                        if let Some(Bind::Modes(mut v)) = modes.clone() { v.remove("loop"); binds.insert("=".to_string(), Bind::Modes(v)); }
+                        let mut result = Ok(());
                         let mut i = 0;
-                        while i < a as i64 {
-                            get_all_instructions(body.0.clone(), instructions, binds);
+                        while i < a as i64 && result.is_ok() {
+                            binds.insert(INDEX.to_string(), Bind::Item(Box::new(Item::Const(Value::Number(i as f64)))));
+                            result = get_all_instructions(&b.0, instructions, binds);
                             i += 1;
                         }
+                        match index { Some(x) => binds.insert(INDEX.to_string(), x), None => binds.remove(INDEX) }
+                        match modes { Some(x) => binds.insert("=".to_string(), x), None => binds.remove("=") }
 
-                        return body.1
+                        result.map_err(skip(b.1))?;
+                        return Ok(b.1)
                     }
+
+                    // counted loop at runtime: index is increased at the start, so `continue` doesn't skip it
+                    let (n, i) = (temp("count"), temp("index"));
+                    let count = match count { Some(x) => x.expr(), None => Expr::new(ExprKind::Builtin("int".to_string(), vec![expr(times, binds)]), Span::default()) };
+                    instructions.push(Item::stmt(StmtKind::Let(n.clone(), count)));
+                    instructions.push(Item::stmt(StmtKind::Let(i.clone(), Value::Number(-1.0).expr())));
+                    let mut body = loop_body(&b.0, binds, vec![(INDEX.to_string(), Bind::Var(i.clone(), None))]).map_err(skip(b.1))?;
+                    let get = |x: &String| Box::new(Expr::new(ExprKind::Get(x.clone()), Span::default()));
+                    let stmt = |x| Stmt::new(x, Span::default());
+                    body.stmts.splice(0..0, [
+                        stmt(StmtKind::Let(i.clone(), Expr::new(ExprKind::Binary(BinOp::Add, get(&i), Box::new(Value::Number(1.0).expr())), Span::default()))),
+                        stmt(StmtKind::If(vec![(Expr::new(ExprKind::Binary(BinOp::Ge, get(&i), get(&n)), Span::default()), Body { stmts: vec![stmt(StmtKind::Break)] })], None))
+                    ]);
+                    instructions.push(Item::stmt(StmtKind::Loop(body)));
+                    return Ok(b.1)
                 }
                 "do" => {
-                    let body = parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")).unwrap();
-                    instructions.push(Value::Raw(Box::new(Value::Do(body.0, 0))));
-                    return body.1
+                    let body = parse_body(tokens, 1, ("{", "}"))?;
+                    let quote = quotation(&body.0, binds);
+                    instructions.push(Item::Quote(body.0.into_owned(), quote));
+                    return Ok(body.1)
                 }
                 "unwrap" => {
-                    if let Value::Raw(a) = instructions.pop().unwrap() {
-                        if let Value::Do(a, _) = *a {
-                            get_all_instructions(a, instructions, binds);
+                    match instructions.pop() {
+                        Some(Item::Quote(a, _)) => { get_all_instructions(&a, instructions, binds).map_err(skip(1))?; }
+                        Some(_) => { bc_error!(token, "`unwrap` expects quotation known at compile time", "create quotation with `do {...}`"); }
+                        None => { bc_error!(token, "stack underflow: `unwrap` expects quotation", "create quotation with `do {...}`"); }
+                    }
+                }
+                "call" => {
+                    let q = pop_operand(instructions, token, "a quotation")?;
+                    call(q, instructions, binds).map_err(skip(1))?;
+                }
+                // combinators: run quotations around values, which are put back after them
+                "dip" | "keep" => {
+                    let q = pop_operand(instructions, token, "a quotation")?;
+                    let x = once(pop_operand(instructions, token, "a value and a quotation")?, instructions, &token.value, binds);
+                    if token.value == "keep" { instructions.push(x.clone()); }
+                    call(q, instructions, binds).map_err(skip(1))?;
+                    instructions.push(x);
+                }
+                "bi" | "tri" | "cleave" => {
+                    let quotes = if token.value == "cleave" {
+                        match pop_operand(instructions, token, "an array of quotations")?.items() {
+                            Some(x) => x,
+                            None => bc_error!(token, "`cleave` expects array of quotations known at compile time", "write quotations as array literal: `[ do {...} do {...} ] cleave`")
+                        }
+                    } else {
+                        let n = if token.value == "bi" { 2 } else { 3 };
+                        let mut quotes = vec![];
+                        for _ in 0..n { quotes.push(pop_operand(instructions, token, &format!("a value and {n} quotations"))?); }
+                        quotes.reverse();
+                        quotes
+                    };
+                    let x = once(pop_operand(instructions, token, "a value and quotations")?, instructions, &token.value, binds);
+                    for q in quotes {
+                        instructions.push(x.clone());
+                        call(q, instructions, binds).map_err(skip(1))?;
+                    }
+                }
+                "bi@" => {
+                    let q = pop_operand(instructions, token, "a quotation")?;
+                    let y = pop_operand(instructions, token, "two values and a quotation")?;
+                    let x = once(pop_operand(instructions, token, "two values and a quotation")?, instructions, "bi", binds);
+                    let y = once(y, instructions, "bi", binds);
+                    for x in [x, y] {
+                        instructions.push(x);
+                        call(q.clone(), instructions, binds).map_err(skip(1))?;
+                    }
+                }
+                "curry" => {
+                    let q = pop_operand(instructions, token, "a quotation")?;
+                    let x = pop_operand(instructions, token, "a value and a quotation")?;
+                    quote_operands(&[&q], token, instructions, binds)?;
+                    let Item::Quote(tokens, _) = q else {
+                        let x = ExprKind::Curry(Box::new(expr(x, binds)), Box::new(expr(q, binds)));
+                        instructions.push(Item::expr(x));
+                        return Ok(1)
+                    };
+                    // value is evaluated now and quotation gets a word pushing it
+                    let name = temp("curry");
+                    let x = expr(x, binds);
+                    instructions.push(Item::stmt(StmtKind::Let(name.clone(), x)));
+                    binds.insert(name.clone(), Bind::Var(name.clone(), None));
+                    let tokens: Vec<Token> = std::iter::once(word_token(name, token)).chain(tokens).collect();
+                    let quote = quotation(&tokens, binds);
+                    instructions.push(Item::Quote(tokens, quote));
+                }
+                "sum" | "flat" | "inc" | "dec" | "int" | "array" => {
+                    let a = pop_operand(instructions, token, "a value")?;
+                    let x = match known(a.clone(), binds).constant().and_then(|x| x.word_static(&token.value)) {
+                        Some(x) => Item::Const(x),
+                        None => Item::expr(ExprKind::Builtin(token.value.clone(), vec![expr(a, binds)]))
+                    };
+                    instructions.push(x);
+                }
+                "concat" => {
+                    let b = pop_operand(instructions, token, "two values")?;
+                    let a = pop_operand(instructions, token, "two values")?;
+                    let x = match (known(a.clone(), binds).constant(), known(b.clone(), binds).constant()) {
+                        (Some(x), Some(y)) => x.concat_static(y),
+                        _ => None
+                    };
+                    let x = match x {
+                        Some(x) => Item::Const(x),
+                        None => Item::expr(ExprKind::Builtin(token.value.clone(), exprs(vec![a, b], binds)))
+                    };
+                    instructions.push(x);
+                }
+                "map" | "filter" | "reduce" | "fold" | "each" | "each-index" | "any" | "all" | "find" | "count" => {
+                    let word = if token.value == "fold" { "reduce" } else { token.value.as_str() };
+                    let q = quote_arg(pop_operand(instructions, token, "a quotation")?, token, binds)?;
+                    let init = if word == "reduce" { Some(pop_operand(instructions, token, "an array, an initial value and a quotation")?) } else { None };
+                    let arr = pop_operand(instructions, token, "an array and a quotation")?;
+                    if let (Some(items), Item::Quote(tokens, _)) = (arr.clone().items(), &q) {
+                        if arr.is_pure() && unroll(word, &items, tokens, init.clone(), instructions, binds).map_err(skip(1))? { return Ok(1) }
+                    }
+                    let args = exprs([Some(arr), init, Some(q)].into_iter().flatten().collect(), binds);
+                    binds.forget_all();
+                    instructions.push(Item::Pops(Some(Stmt::new(StmtKind::ArrayOp(word.to_string(), args), Span::default())), vec![]));
+                }
+                "compose" => {
+                    let q = pop_operand(instructions, token, "a quotation")?;
+                    let p = pop_operand(instructions, token, "two quotations")?;
+                    quote_operands(&[&p, &q], token, instructions, binds)?;
+                    match (p, q) {
+                        (Item::Quote(mut p, _), Item::Quote(q, _)) => {
+                            p.extend(q);
+                            let quote = quotation(&p, binds);
+                            instructions.push(Item::Quote(p, quote));
+                        }
+                        (p, q) => {
+                            let x = ExprKind::Compose(Box::new(expr(p, binds)), Box::new(expr(q, binds)));
+                            instructions.push(Item::expr(x))
                         }
-                        else { instructions.push(*a); }
                     }
                 }
                 "do:add_tokens" => {
-                    if let Value::String(a) = popv(instructions).unwrap() {
-                        if let Value::Raw(b) = instructions.pop().unwrap() {
-                            if let Value::Do(mut b, _) = *b {
-                                b.append(&mut lex(a, get_lexer_rules()));
-                                instructions.push(Value::Do(b, 0));
-                                return 1
-                            }
+                    if let Some(Item::Const(Value::String(a))) = popv(instructions) {
+                        if let Some(Item::Quote(mut b, _)) = instructions.pop() {
+                            let mut tokens = lex(a, vec![]);
+                            check_lexed(&tokens, binds)?;
+                            b.append(&mut tokens);
+                            let quote = quotation(&b, binds);
+                            instructions.push(Item::Quote(b, quote));
+                            return Ok(1)
                         }
                     }
                     bc_error!(token, "cannot parse raw value. Make sure you are using it like `do {...} \"...\" do:add_tokens`");
                 }
                 "macro" | "macro:b:" => {
-                    if let TokenKind::Keyword = tokens[1].typ {
-                        let name = tokens[1].value.clone();
-                        let body = parse_pair_symbols(&tokens[2..].to_vec(), ("#!", "!#")).expect("Cannot parse body of macro. Make sure that body is covered in #!..#!");
-                        // if let Some(x) = body.0.iter().find(|x| x.value == name) {
-                        //     bc_error!(x, "unavoidable infinite self-expansion");
-                        // }
-                        binds.insert(name, Value::Do(body.0, (token.value == "macro:b:") as i32));
-                        return body.1 + 1
-                    }
+                    let Some(name) = tokens.get(1).filter(|x| x.typ == TokenKind::Keyword) else { bc_error!(token, "expected macro name after `macro`"); };
+                    let body = parse_body(tokens, 2, ("#!", "!#"))?;
+                    // if let Some(x) = body.0.iter().find(|x| x.value == name) {
+                    //     bc_error!(x, "unavoidable infinite self-expansion");
+                    // }
+                    binds.insert(name.value.clone(), Bind::Macro(body.0.into_owned(), token.value == "macro:b:"));
+                    return Ok(body.1)
                 }
                 "fn" => {
-                    if let TokenKind::Keyword = tokens[1].typ {
-                        let (args, last) = parse_args(tokens, 2);
-                        let body = parse_pair_symbols(&tokens[last..].to_vec(), ("{", "}")).unwrap();
-                        binds.insert(tokens[1].value.clone(), Value::TCall(tokens[1].value.clone(), args.len()));
-                        let mut binds = binds.clone();
-                        for (name, value) in binds.clone().iter() { if let Value::Get(_) = value { binds.remove(name); } } // remove variables that out of scope of variable
-                        binds.insert("=".to_string(), Value::ParseModes(HashSet::from(["fn".to_string()])));
-                        // args.iter().for_each(|i| { binds.insert(i.clone(), Value::Get(i.clone())); });
-                        args.iter().for_each(|i| { binds.insert(i.clone(), Value::Ref(Box::new(Value::Get(i.clone())))); });
-                        instructions.push(Value::Fn(tokens[1].value.clone(), args, get_all_instructions(body.0, &mut vec![], &mut binds)));
-
-                        return body.1 + last
+                    let Some(name) = tokens.get(1).filter(|x| x.typ == TokenKind::Keyword) else { bc_error!(token, "expected function name after `fn`"); };
+                    let (args, last) = parse_args(tokens, 2);
+                    let body = parse_body(tokens, last, ("{", "}"))?;
+                    binds.insert(name.value.clone(), Bind::Fn(args.len()));
+                    // variables around the function are out of its scope
+                    let statements = binds.scoped(Scope::Function, |binds| {
+                        binds.insert("=".to_string(), Bind::Modes(HashSet::from(["fn".to_string()])));
+                        args.iter().for_each(|i| {
+                            let x = Box::new(Expr::new(ExprKind::Get(i.clone()), Span::default()));
+                            binds.insert(i.clone(), Bind::Item(Box::new(Item::expr(ExprKind::Ref(x)))));
+                        });
+                        statements(&body.0, binds)
+                    }).0;
+                    instructions.push(Item::stmt(StmtKind::Fn(name.value.clone(), args, statements.map_err(skip(body.1))?)));
+
+                    return Ok(body.1)
+                }
+                "dup" | "drop" | "swap" | "over" | "rot" | "-rot" | "nip" | "tuck" | "2dup" | "2drop" => {
+                    if !shuffle(&token.value, instructions, binds) {
+                        get_mode!(binds, {
+                            return Err(Diagnostic::error(token, format!("stack underflow: `{}` expects {}", token.value, values(stack_effect(&token.value).unwrap().0)))
+                                .note("functions can't use values pushed outside of them").into())
+                        }, "fn");
                     }
                 }
-                "type" => { let a = popv(instructions).unwrap(); instructions.push(Value::Type(Box::new(a))) }
+                "type" => {
+                    let a = pop_operand(instructions, token, "a value")?;
+                    let a = expr(a, binds);
+                    instructions.push(Item::expr(ExprKind::Type(Box::new(a))))
+                }
                 "pick" => {
-                    let index = popv(instructions).unwrap();
-                    let arr = popv(instructions).unwrap();
-                    if arr.is_static() && index.is_static() { instructions.push(arr[index].clone()); return 1 }
-                    instructions.push(Value::Pick(Box::new(arr), Box::new(index)));
+                    let index = pop_operand(instructions, token, "an index")?;
+                    let arr = pop_operand(instructions, token, "a collection and an index")?;
+                    let x = match (known(arr.clone(), binds).constant(), known(index.clone(), binds).constant()) {
+                        (Some(x), Some(y)) => pick(&x, &y),
+                        _ => None
+                    };
+                    let x = match x {
+                        Some(x) => Item::Const(x),
+                        None => Item::expr(ExprKind::Pick(Box::new(expr(arr, binds)), Box::new(expr(index, binds))))
+                    };
+                    instructions.push(x);
                 }
                 "set" => { // can be implemented with rust_exec instruction
-                    let value = Box::new(popv(instructions).unwrap());
-                    let index = Box::new(popv(instructions).unwrap());
-                    let arr = Box::new(popv(instructions).unwrap());
-                    instructions.push(Value::Set(arr, index, value));
+                    let value = pop_operand(instructions, token, "a value")?;
+                    let index = pop_operand(instructions, token, "an index and a value")?;
+                    let arr = pop_operand(instructions, token, "a collection, an index and a value")?;
+                    if let Some(name) = variable(&arr) { binds.forget(name); }
+                    let x = StmtKind::Set(expr(arr, binds), expr(index, binds), expr(value, binds));
+                    instructions.push(Item::stmt(x));
                 }
                 "mov" => {
-                    if let TokenKind::Keyword = tokens[1].typ {
+                    if let Some(name) = tokens.get(1).filter(|x| x.typ == TokenKind::Keyword) {
                         get_mode!(binds, {
-                            binds.insert(tokens[1].value.clone(), Value::Get(tokens[1].value.clone()));
-                            instructions.push(Value::Mov(tokens[1].value.clone()));
-                            return 2
+                            binds.insert(name.value.clone(), Bind::Var(name.value.clone(), None));
+                            instructions.push(Item::stmt(StmtKind::Mov(name.value.clone())));
+                            return Ok(2)
                         }, "array");
+                        bc_error!(token, "`mov` is not available out of the arrays");
                     }
-                    bc_error!(token, "`mov` is not available out of the arrays");
+                    bc_error!(token, "expected variable name after `mov`");
                 }
                 "push" => { // can be implemented with rust_exec instruction
-                    let value = Box::new(popv(instructions).unwrap());
-                    let parent = Box::new(popv(instructions).unwrap());
-                    instructions.push(Value::Push(parent, value));
+                    let value = pop_operand(instructions, token, "a value")?;
+                    let parent = pop_operand(instructions, token, "an array and a value")?;
+                    if let Some(name) = variable(&parent) { binds.forget(name); }
+                    let x = StmtKind::Push(expr(parent, binds), expr(value, binds));
+                    instructions.push(Item::stmt(x));
                 }
                 "dict" => {
-                    let Value::Array(v) = popv(instructions).unwrap() else { todo!() }; let Value::Array(k) = popv(instructions).unwrap() else { todo!() };
-                    instructions.push(Value::Dict(k, v));
-                    return 1
+                    let v = pop_operand(instructions, token, "arrays of keys and values")?;
+                    let k = pop_operand(instructions, token, "arrays of keys and values")?;
+                    let Some(k) = k.items() else { bc_error!(token, "`dict` expects array of keys", "write keys as array literal: `[ ... ] [ ... ] dict`"); };
+                    let Some(v) = v.items() else { bc_error!(token, "`dict` expects array of values", "write values as array literal: `[ ... ] [ ... ] dict`"); };
+                    if k.iter().chain(&v).all(|x| x.is_static()) && k.len() != v.len() {
+                        return Err(Diagnostic::error(token, "`dict` expects the same amount of keys and values")
+                            .note(format!("found {} keys and {} values", k.len(), v.len())).into())
+                    }
+                    let x = ExprKind::Dict(body(k, binds), body(v, binds));
+                    instructions.push(Item::expr(x));
+                    return Ok(1)
                 }
                 "println" => {
-                    let x = popv(instructions).expect("println cannot pop the thing");
-                    instructions.push(Value::Println(Box::new(x)));
-                    return 1
+                    let x = pop_operand(instructions, token, "a value to print")?;
+                    let x = expr(x, binds);
+                    instructions.push(Item::stmt(StmtKind::Println(x)));
+                    return Ok(1)
                 }
                 ":rust!" | ":rust!:" => {
-                    if let Some(Value::Array(a)) = popv(instructions) {
-                        match token.value.as_str() {
-                            ":rust!" => instructions.push(Value::RustBinding(a)),
-                            ":rust!:" => instructions.push(Value::RustReturnableBinding(a)),
-                            _ => {}
-                        };
-                    }
-                    // error handling
+                    let Some(a) = popv(instructions).and_then(|x| x.items()) else { bc_error!(token, format!("`{}` expects array of code parts", token.value)); };
+                    // rust code can change any variable
+                    binds.forget_all();
+                    let a = exprs(a, binds);
+                    match token.value.as_str() {
+                        ":rust!" => instructions.push(Item::stmt(StmtKind::Rust(a))),
+                        ":rust!:" => instructions.push(Item::expr(ExprKind::Rust(a))),
+                        _ => {}
+                    };
                 }
                 "use" => {
-                    let path = popv(instructions).expect("cannot get path");
-                    if let Value::String(path) = path {
-                        if let Some(_) = binds.get(&path) { return 1; }
-                        binds.insert(path.clone(), Value::Undefined);
-                        let path = absolute(path).unwrap();
-                        
-                        let mut code = String::new();
-                        let _ = File::open(path).unwrap().read_to_string(&mut code);
-                        let tokens = lex(code, get_lexer_rules());
-                        get_all_instructions(tokens, instructions, binds);
-                    }
+                    let path = pop_operand(instructions, token, "a path")?;
+                    let Item::Const(Value::String(path)) = path else { bc_error!(token, "`use` expects path as a string"); };
+                    if binds.contains_key(&path) { return Ok(1); }
+                    binds.insert(path.clone(), Bind::Item(Box::new(Item::Const(Value::Undefined))));
+
+                    let code = absolute(&path).and_then(|path| { let mut code = String::new(); File::open(path)?.read_to_string(&mut code)?; Ok(code) });
+                    let code = match code {
+                        Ok(x) => x,
+                        Err(e) => { bc_error!(token, format!("cannot read `{path}`: {e}")); }
+                    };
+                    let mut tokens = lex(code, vec![]);
+                    tokens.iter_mut().for_each(|i| i.loc = path.clone());
+                    check_lexed(&tokens, binds)?;
+                    get_all_instructions(&tokens, instructions, binds).map_err(skip(1))?;
                 }
-                ":current_code_place!:" => { instructions.push(Value::String(format!("{}:{}", token.line, token.col))) }
+                ":current_code_place!:" => { instructions.push(Item::Const(Value::String(format!("{}:{}", token.line, token.col)))) }
                 _ => {
                     if let Some(x) = binds.get(&token.value) {
-                        if let Value::Do(x, offset) = x {
-                            let x = if offset == &1 {
-                                let (mut body, len) = parse_pair_symbols(&tokens[1..].to_vec(), ("{", "}")).unwrap();
-                                body.insert(0, Token { value: "do".to_string(), typ: TokenKind::Keyword, line: token.line, col: token.col, loc: token.loc.clone() });
-                                body.insert(1, Token { value: "{".to_string(), typ: TokenKind::CurlyBracket, line: token.line, col: token.col, loc: token.loc.clone() });
-                                body.push(Token { value: "}".to_string(), typ: TokenKind::CurlyBracket, line: token.line, col: token.col, loc: token.loc.clone() });
-                                body.extend(x.iter().cloned());
-                                body.extend(tokens[len..].iter().cloned());
-                                body
+                        if let Bind::Macro(x, body) = &x {
+                            // macro is replaced by its tokens, which can take tokens after it
+                            let (expansion, len) = if *body {
+                                let (body, len) = parse_body(tokens, 1, ("{", "}"))?;
+                                let mut expansion = vec![
+                                    Token { value: "do".to_string(), typ: TokenKind::Keyword, line: token.line, col: token.col, loc: token.loc.clone(), doc: None },
+                                    Token { value: "{".to_string(), typ: TokenKind::CurlyBracket, line: token.line, col: token.col, loc: token.loc.clone(), doc: None }
+                                ];
+                                expansion.extend(body.iter().cloned());
+                                expansion.push(Token { value: "}".to_string(), typ: TokenKind::CurlyBracket, line: token.line, col: token.col, loc: token.loc.clone(), doc: None });
+                                expansion.extend(x.iter().cloned());
+                                (expansion, len)
                             } else {
-                                let mut x: Vec<Token> = x.iter().map(|x| Token { typ: x.typ, value: x.value.clone(), line: token.line, col: token.col, loc: token.loc.clone() }).clone().collect();
-                                x.extend(tokens[1..].iter().cloned());
-                                dbg!(&x);
-                                x
+                                (x.iter().map(|x| Token { typ: x.typ, value: x.value.clone(), line: token.line, col: token.col, loc: token.loc.clone(), doc: None }).collect(), 1)
                             };
-                            let len = x.len();
-                            get_all_instructions(x, instructions, binds);
-                            return len;
+                            let (used, errors) = generate(&Tokens::then(&expansion, &tokens.skip(len)), expansion.len(), instructions, binds, &mut vec![]);
+                            let len = len + used - expansion.len();
+                            if !errors.is_empty() { return Err(Failure { diagnostics: errors, skip: len }) }
+                            return Ok(len);
                         }
-                        else if let Value::TCall(name, n) = x {
+                        else if let Bind::Fn(n) = &x {
+                            let name = &token.value;
                             let mut a = vec![];
-                            for _ in 0 .. *n { a.push(popv(instructions).unwrap()); }
+                            for _ in 0 .. *n {
+                                let Some(x) = popv(instructions) else {
+                                    return Err(Diagnostic::error(token, format!("stack underflow: function `{name}` expects {n} arguments"))
+                                        .note(format!("only {} arguments are available", a.len())).into())
+                                };
+                                a.push(x);
+                            }
                             a.reverse();
-                            instructions.push(Value::Call(name.clone(), a));
+                            // quotation passed to the function can change variables
+                            if !a.iter().all(|x| known(x.clone(), binds).is_static()) { binds.forget_all(); }
+                            let a = exprs(a, binds);
+                            instructions.push(Item::expr(ExprKind::Call(name.clone(), a)));
                         }
-                        else { instructions.push(x.clone()); }
+                        else if let Bind::Var(name, _) = x { instructions.push(Item::expr(ExprKind::Get(name))); }
+                        else if let Bind::Item(x) = x { instructions.push(*x); }
                     }
-                    else { bc_error!(token, format!("unknown keyword: `{}`", token.value)); }
+                    else { bc_error!(token, format!("unknown keyword: `{}`", token.value), "define it with `-> name`, `fn name ... {...}` or `macro name #!...!#`"); }
                 }
             }
             
-            return 1
+            Ok(1)
         }
         TokenKind::CurlyBracket => {
             if token.value == "{" {
-                let b = parse_pair_symbols(tokens, ("{", "}")).expect(&format!("{}:{}", token.line, token.col));
-                let binds = &mut binds.clone();
+                let b = parse_body(tokens, 0, ("{", "}"))?;
                 let l = instructions.len();
-                get_all_instructions(b.0, instructions, binds);
-                let body = if instructions.len() <= l { instructions.drain(instructions.len()-1..l-1).collect() } else { instructions.drain(l..instructions.len()).collect() };
-                instructions.push(Value::Block(body));
-                return b.1
+                binds.scoped(Scope::Block, |binds| get_all_instructions(&b.0, instructions, binds)).0.map_err(skip(b.1))?;
+                let body = if instructions.len() <= l { instructions.drain(instructions.len().saturating_sub(1)..l.saturating_sub(1)).collect() } else { instructions.drain(l..instructions.len()).collect() };
+                instructions.push(Item::Block(body));
+                return Ok(b.1)
             }
-            return 1
+            bc_error!(token, "unexpected `}`", "remove it or add matching `{`");
         }
         TokenKind::Bracket => {
-            match parse_pair_symbols(tokens, ("[", "]")) {
-                Some(b) => {
-                    let binds = &mut binds.clone();
-                    binds.insert("=".to_string(), Value::ParseModes(HashSet::from(["array".to_string()])));
-                    let body = get_all_instructions(b.0, &mut vec![], binds);
-                    instructions.push(Value::Array(body));
-                    return b.1
-                }
-                None => {}
+            if token.value == "[" {
+                let b = parse_body(tokens, 0, ("[", "]"))?;
+                let mut body = vec![];
+                binds.scoped(Scope::Block, |binds| {
+                    binds.insert("=".to_string(), Bind::Modes(HashSet::from(["array".to_string()])));
+                    get_all_instructions(&b.0, &mut body, binds)
+                }).0.map_err(skip(b.1))?;
+                instructions.push(Item::array(body));
+                return Ok(b.1)
             }
-            return 1
+            bc_error!(token, "unexpected `]`", "remove it or add matching `[`");
+        }
+        TokenKind::Unknown => {
+            if binds.contains_key(LEX_WARNINGS) { return Ok(1) }
+            Err(lex_error(token, Severity::Error).into())
         }
-        _ => { return 1 }
+        _ => Ok(1)
+    }
+}
+
+// executes quotation: expands it in place when it's known at compile time, or calls it at runtime
+fn call (quote: Item, instructions: &mut Vec<Item>, binds: &mut Binds) -> Result<(), Vec<Diagnostic>> {
+    if let Item::Quote(tokens, _) = quote {
+        get_all_instructions(&tokens, instructions, binds)?;
+        return Ok(())
     }
+    // values on top of the stack are pushed to the runtime stack before the call, so the quotation can take them
+    let at = instructions.iter().rposition(|x| !x.is_value()).map_or(0, |i| i + 1);
+    let args = instructions.split_off(at);
+    binds.forget_all();
+    let x = StmtKind::CallQuote(expr(quote, binds), exprs(args, binds));
+    instructions.push(Item::Pops(Some(Stmt::new(x, Span::default())), vec![]));
+    Ok(())
+}
+
+// quotation argument of array words: `do {...}` or name of a function as a string
+fn quote_arg (x: Item, token: &Token, binds: &mut Binds) -> Result<Item, Failure> {
+    let Item::Const(Value::String(name)) = x else { return Ok(x) };
+    let Some(Bind::Fn(_)) = binds.get(&name) else { bc_error!(token, format!("`{}` expects quotation, but there is no function `{name}`", token.value), "pass a quotation `do {...}` or name of a function"); };
+    let tokens = vec![word_token(name, token)];
+    let quote = quotation(&tokens, binds);
+    Ok(Item::Quote(tokens, quote))
+}
+
+// operands of `curry` and `compose` known at compile time must be quotations. result is pushed anyway, so code after it doesn't report stack underflow
+fn quote_operands (operands: &[&Item], token: &Token, instructions: &mut Vec<Item>, binds: &Binds) -> Result<(), Failure> {
+    let errors: Vec<Diagnostic> = operands.iter().filter_map(|x| known((*x).clone(), binds).constant()).map(|x| {
+        Diagnostic::error(token, format!("`{}` expects quotation, found {}", token.value, to_runtime(&x).cast_type())).help("create quotation with `do {...}`")
+    }).collect();
+    if errors.is_empty() { return Ok(()) }
+    instructions.push(Item::Const(Value::Undefined));
+    Err(Failure { diagnostics: errors, skip: 1 })
+}
+
+// expands array word for array and quotation known at compile time. returns false when results of the quotation aren't known
+fn unroll (word: &str, items: &[Item], tokens: &[Token], init: Option<Item>, instructions: &mut Vec<Item>, binds: &mut Binds) -> Result<bool, Vec<Diagnostic>> {
+    match word {
+        "each" | "each-index" | "reduce" => {
+            instructions.extend(init);
+            for (i, x) in items.iter().enumerate() {
+                instructions.push(x.clone());
+                if word == "each-index" { instructions.push(Item::Const(Value::Number(i as f64))); }
+                get_all_instructions(tokens, instructions, binds)?;
+            }
+        }
+        "map" => {
+            // the same as array literal `[ x q y q ... ]`
+            let mut body = vec![];
+            binds.scoped(Scope::Block, |binds| {
+                binds.insert("=".to_string(), Bind::Modes(HashSet::from(["array".to_string()])));
+                items.iter().try_for_each(|x| { body.push(x.clone()); get_all_instructions(tokens, &mut body, binds) })
+            }).0?;
+            instructions.push(Item::array(body));
+        }
+        _ => {
+            let mut results = vec![];
+            for x in items {
+                let mut body = vec![x.clone()];
+                binds.scoped(Scope::Block, |binds| get_all_instructions(tokens, &mut body, binds)).0?;
+                match body.as_slice() {
+                    [x] if x.is_static() => results.push(x.constant().unwrap().cast_bool_static()),
+                    _ => return Ok(false)
+                }
+            }
+            let mut passed = items.iter().zip(&results).filter(|x| *x.1).map(|x| x.0.clone());
+            instructions.push(match word {
+                "filter" => Item::array(passed.collect()),
+                "any" => Item::Const(Value::Boolean(results.contains(&true))),
+                "all" => Item::Const(Value::Boolean(!results.contains(&false))),
+                "find" => passed.next().unwrap_or(Item::Const(Value::Undefined)),
+                _ => Item::Const(Value::Number(passed.count() as f64))
+            });
+        }
+    }
+    Ok(true)
+}
+
+// name of variable which the value is
+fn variable (x: &Item) -> Option<&str> {
+    match x {
+        Item::Expr(Expr { kind: ExprKind::Get(name), .. }) => Some(name),
+        _ => None
+    }
+}
+
+// value of variable known at compile time, so operations with it are folded like with literals
+fn known (x: Item, binds: &Binds) -> Item {
+    match x {
+        Item::Expr(Expr { kind: ExprKind::Get(name), span }) => match binds.get(&name) {
+            Some(Bind::Var(_, Some(x))) => Item::Const(x),
+            _ => Item::Expr(Expr { kind: ExprKind::Get(name), span })
+        },
+        Item::Array(x) => Item::array(x.into_iter().map(|x| known(x, binds)).collect()),
+        x => x
+    }
+}
+
+// value computed at compile time, or left with variables when their values aren't known
+fn fold (x: Item, binds: &Binds) -> Item {
+    let known = known(x.clone(), binds);
+    if known.is_static() { known } else { x }
+}
+
+// `a b op` computed at compile time when both operands are known
+fn fold_binary (a: Item, b: Item, op: BinOp, binds: &mut Binds) -> Item {
+    if let (Some(x), Some(y)) = (known(a.clone(), binds).constant(), known(b.clone(), binds).constant()) {
+        if let Some(x) = binary(&x, &y, op.symbol()) { return Item::Const(x) }
+    }
+    binary_expr(a, b, op, binds)
+}
+
+fn binary_expr (a: Item, b: Item, op: BinOp, binds: &mut Binds) -> Item {
+    let (a, b) = (expr(a, binds), expr(b, binds));
+    Item::expr(ExprKind::Binary(op, Box::new(a), Box::new(b)))
+}
+
+// `/` or `%` of values known at compile time, which divides a number by zero
+fn divides_by_zero (a: &Item, b: &Item) -> bool {
+    if !a.is_static() || !b.is_static() { return false }
+    match (a.clone().items(), b.clone().items()) {
+        (Some(x), Some(y)) => x.iter().zip(&y).any(|(x, y)| divides_by_zero(x, y)),
+        _ => matches!(b, Item::Const(Value::Number(x)) if *x == 0.0)
+    }
+}
+
+// body of a loop with `locals` bound in it. it's repeated, so variables it changes aren't known anywhere in it: body is generated again until they are all found
+fn loop_body (tokens: &[Token], binds: &mut Binds, locals: Vec<(String, Bind)>) -> Result<Body, Vec<Diagnostic>> {
+    loop {
+        let (body, changed) = binds.scoped(Scope::Block, |binds| {
+            add_mode!(binds, "loop");
+            locals.iter().for_each(|(name, x)| binds.insert(name.clone(), x.clone()));
+            statements(tokens, binds)
+        });
+        if !changed { return body }
+    }
+}
+
+// whether tokens have `break` or `continue`, also in macros they use
+fn jumps (tokens: &[Token], binds: &Binds, seen: &mut HashSet<String>) -> bool {
+    tokens.iter().filter(|x| x.typ == TokenKind::Keyword).any(|x| match x.value.as_str() {
+        "break" | "continue" => true,
+        name => match binds.get(name) {
+            Some(Bind::Macro(body, _)) if seen.insert(name.to_string()) => jumps(&body, binds, seen),
+            _ => false
+        }
+    })
+}
+
+// body of if-statement, which has its own scope and may not run
+fn scoped (tokens: &[Token], binds: &mut Binds) -> Result<Vec<(Item, Option<Span>)>, Vec<Diagnostic>> {
+    binds.scoped(Scope::Block, |binds| instructions_on(vec![], tokens, binds)).0
+}
+
+// body of statically passed if-statement becomes a block, which can be used as a value
+fn without_spans (body: Vec<(Item, Option<Span>)>) -> Vec<Item> {
+    body.into_iter().map(|x| x.0).collect()
+}
+
+// if-statement which can be continued by `else if` and `else`
+fn is_open (x: &Item) -> bool {
+    matches!(x, Item::Stmt(Stmt { kind: StmtKind::If(_, None), .. }) | Item::ElseIf(Stmt { kind: StmtKind::If(_, None), .. }) | Item::PassedIf | Item::FailedIf)
+}
+
+// keyword token generated for the code at `at`
+fn word_token (value: String, at: &Token) -> Token {
+    Token { value, typ: TokenKind::Keyword, line: at.line, col: at.col, loc: at.loc.clone(), doc: None }
+}
+
+// value which can be used several times: value with side effects is evaluated once, into temporary variable
+fn once (x: Item, instructions: &mut Vec<Item>, prefix: &str, binds: &mut Binds) -> Item {
+    if x.is_pure() { return x }
+    let name = temp(prefix);
+    let x = expr(x, binds);
+    instructions.push(Item::stmt(StmtKind::Let(name.clone(), x)));
+    Item::expr(ExprKind::Get(name))
+}
+
+// shuffles values known at compile time, or emits `StmtKind::StackOp` to do it at runtime. returns false in the last case
+fn shuffle (word: &str, instructions: &mut Vec<Item>, binds: &mut Binds) -> bool {
+    let (n, result) = stack_effect(word).unwrap();
+
+    let mut values = vec![];
+    while values.len() < n {
+        let Some(x) = popv(instructions) else { break };
+        values.push(x);
+    }
+    values.reverse();
+    if values.len() < n {
+        instructions.extend(values);
+        instructions.push(Item::Pops(Some(Stmt::new(StmtKind::StackOp(word.to_string()), Span::default())), vec![]));
+        return false
+    }
+
+    // values with side effects are evaluated once, in their order
+    let values: Vec<Item> = values.into_iter().map(|x| once(x, instructions, word, binds)).collect();
+    instructions.extend(result.iter().map(|i| values[*i].clone()));
+    true
 }
 
 // pop valuе. use it for non-raw execution of instruction (e.g. sum numbers, println...)
-pub fn popv (instructions: &mut Vec<Value>) -> Option<Value> {
+fn popv (instructions: &mut Vec<Item>) -> Option<Item> {
     let x = instructions.pop()?;
 
     match x {
-        Value::Array(_) | Value::Number(_) | Value::String(_) | Value::Boolean(_) | Value::NumOp(_, _, _) | Value::LogOp(_, _, _) | Value::Get(_) | Value::Call(_, _) | Value::Dict(_, _)
-        | Value::Pick(_, _) | Value::Block(_) | Value::Type(_) | Value::RustReturnableBinding(_)
-        | Value::Ref(_) | Value::Undefined => Some(x),
-        Value::Else(_) => {
-            let mut block: Vec<Value> = vec![x];
-            let mut v = instructions.pop()?;
-            loop {
-                block.push(v.clone());
-                if let Value::If(_, _) = v { break; }
-                v = instructions.pop()?;
-            }
+        x if x.is_value() => Some(x),
+        // values below it are on the runtime stack, so the value is popped from there right after the statement
+        Item::Pops(statement, mut vars) => {
+            let name = temp("pop");
+            vars.push(name.clone());
+            instructions.push(Item::Pops(statement, vars));
+            Some(Item::expr(ExprKind::Get(name)))
+        }
+        // if-statement with `else` gives a value
+        Item::Stmt(Stmt { kind: StmtKind::If(_, Some(_)), .. }) | Item::ElseIf(Stmt { kind: StmtKind::If(_, Some(_)), .. }) => {
+            let mut block = vec![x];
+            while let Some(Item::ElseIf(_)) = block.last() { block.push(instructions.pop()?); }
             block.reverse();
-            
-            Some(Value::Block(block))
+            Some(Item::Block(block))
         }
         _ => {
             let y = popv(instructions);
             instructions.push(x);
-            return y
+            y
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use crate::lexer::lex;
+    use crate::bytecode::{binds::{Bind, Binds}, ir::{ExprKind, Stmt, StmtKind}, item::Item, value::Value};
+    use crate::alt::value::Value as Data;
+    use crate::diagnostic::Severity;
+    use crate::interpreter::Interpreter;
+    use super::{get_statements, statements, DEFAULT_UNROLL_LIMIT, LEX_WARNINGS, UNROLL_LIMIT};
+
+    fn generate (code: &str) -> Vec<Stmt> {
+        statements(&lex(code.to_string(), vec![]), &mut Binds::new()).unwrap().stmts
+    }
+
+    #[test]
+    fn macros_take_tokens_after_them () {
+        let last = |code| generate(code).pop().map(|x| x.kind);
+        assert!(matches!(last("macro store #! -> !# 5 store x x 1 +"), Some(StmtKind::Expr(x)) if x.kind == ExprKind::Number(6.0)));
+        assert!(matches!(last("macro:b: apply #! call !# 0 apply { 1 + } 2 *"), Some(StmtKind::Expr(x)) if x.kind == ExprKind::Number(2.0)));
+    }
+
+    // warnings about code lexed by the generator are given to the caller, which knows its source
+    #[test]
+    fn lex_warnings_are_returned () {
+        let mut binds = Binds::new();
+        binds.insert(LEX_WARNINGS.to_string(), Bind::Item(Box::new(Item::Const(Value::Undefined))));
+        statements(&lex("do { 1 } \"2 @ +\" do:add_tokens".to_string(), vec![]), &mut binds).unwrap();
+        let warnings = binds.take_warnings();
+        assert_eq!(warnings.iter().map(|x| (x.severity, x.message.as_str())).collect::<Vec<_>>(), [(Severity::Warning, "unrecognized character `@`")]);
+        assert!(binds.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn loop_sees_variables_changed_in_nested_bodies () {
+        let ir = generate("0 -> x x type -> t loop { x 3 > if { break } t \"number\" = if { x 1 + -> x } } x println");
+        let Some(StmtKind::Loop(body)) = ir.into_iter().map(|x| x.kind).find(|x| matches!(x, StmtKind::Loop(_))) else { panic!("no loop") };
+        // condition of `break` isn't folded with the first value of `x`, which isn't known in the loop
+        let Some(StmtKind::If(branches, _)) = body.stmts.iter().map(|x| &x.kind).find(|x| matches!(x, StmtKind::If(..))) else { panic!("{body}") };
+        assert_eq!(branches[0].1.stmts[0].kind, StmtKind::Break, "{body}");
+    }
+
+    #[test]
+    fn if_chain_is_one_statement_with_scopes () {
+        let ir = get_statements(&lex("fn choose i { 0 -> x i 1 = if { 1 -> x } i 2 = else if { 2 -> x } else { 3 -> x 3 -> y } x }".to_string(), vec![]), &mut Binds::new()).unwrap();
+        let StmtKind::Fn(_, _, body) = &ir.stmts[0].kind else { panic!("{ir}") };
+        let kinds: Vec<&StmtKind> = body.stmts.iter().map(|x| &x.kind).collect();
+        let [StmtKind::Let(..), StmtKind::If(branches, Some(other)), StmtKind::Expr(_)] = kinds[..] else { panic!("{ir}") };
+        assert_eq!(branches.len(), 2);
+        assert!(matches!(&branches[1].1.stmts[0].kind, StmtKind::Assign(x, _) if x == "x"));
+        assert!(matches!(&other.stmts[1].kind, StmtKind::Let(y, _) if y == "y"));
+        assert_eq!((other.stmts[1].span.line, other.stmts[1].span.col), (1, 81));
+        // condition of `else if` keeps its own span
+        assert_eq!((branches[1].0.span.line, branches[1].0.span.col), (1, 42));
+    }
+
+    // only a divisor which is numerically zero is an error, others are left to the runtime
+    #[test]
+    fn division_by_zero_needs_numeric_zero () {
+        let divide = |code: &str| statements(&lex(code.to_string(), vec![]), &mut Binds::new()).is_err();
+        assert!(divide("1 0 /") && divide("1 0 %") && divide("[1 2] [1 0] /"));
+        let errors = statements(&lex("1 0 / println".to_string(), vec![]), &mut Binds::new()).unwrap_err();
+        assert_eq!(errors.iter().map(|x| x.message.as_str()).collect::<Vec<_>>(), ["division by zero"]);
+        assert!(!divide("1 \"a\" /") && !divide("1 true %") && !divide("1 false /") && !divide("[1 2] [1 \"b\"] /"));
+    }
+
+    #[test]
+    fn combinators_take_quotations () {
+        let errors = |code: &str| statements(&lex(code.to_string(), vec![]), &mut Binds::new()).err().unwrap_or_default().into_iter().map(|x| x.message).collect::<Vec<_>>();
+        assert_eq!(errors("1 2 curry call"), ["`curry` expects quotation, found number"]);
+        assert_eq!(errors("do { 1 } [ 2 ] compose println"), ["`compose` expects quotation, found array"]);
+        assert_eq!(errors("\"a\" -> s s do { 1 } compose"), ["`compose` expects quotation, found string"]);
+        assert!(errors("1 do { 2 + } curry do { 3 * } compose call fn f q { do { 1 } q compose } do { 2 } f").is_empty());
+    }
+
+    // stack left by the program, with `times` unrolled up to `limit`
+    fn run (code: &str, limit: usize) -> Result<Vec<Data>, String> {
+        let mut binds = Binds::new();
+        binds.insert(UNROLL_LIMIT.to_string(), Bind::Item(Box::new(Item::Const(Value::Number(limit as f64)))));
+        let ir = get_statements(&lex(code.to_string(), vec![]), &mut binds).map_err(|e| e[0].message.clone())?;
+        let mut interpreter = Interpreter::new();
+        interpreter.run(&ir).map_err(|e| e.message)?;
+        Ok(interpreter.stack)
+    }
+
+    #[test]
+    fn times_jumps_dont_depend_on_unrolling () {
+        for code in ["0 -> c loop { c 1 + -> c 2 times { break } c 5 > if { break } } c", "3 times { index 2 = if { break } index }", "4 times { index 2 % if { continue } index }"] {
+            let (unrolled, runtime) = (run(code, DEFAULT_UNROLL_LIMIT), run(code, 0));
+            assert!(unrolled.is_ok() && unrolled == runtime, "{code}: {unrolled:?} {runtime:?}");
+        }
+        assert_eq!(run("0 -> c loop { c 1 + -> c 2 times { break } c 5 > if { break } } c", DEFAULT_UNROLL_LIMIT), Ok(vec![Data::Number(6.0)]));
+    }
+
+    // program with every kind of body: macro, function, if-statements, loop, array, `times` and quotation
+    fn program (lines: usize) -> String {
+        let (mut code, mut i) = (String::new(), 0);
+        // 15 lines in each part
+        while i * 15 < lines {
+            code += &format!("macro inc{i} #! 1 + !#
+fn f{i} a b {{
+    a b + -> s
+    s 10 > if {{ s 2 * }} else {{ s inc{i} }}
+}}
+0 -> x{i}
+loop {{
+    x{i} 3 >= if {{ break }}
+    x{i} 1 + -> x{i}
+}}
+[ 1 2 3 ] -> arr{i}
+arr{i} 1 pick x{i} f{i} println
+x{i} times {{ index println }}
+do {{ 2 * }} -> q{i}
+5 q{i} call println
+{i} 2 % 0 = if {{ \"even\" println }} else {{ \"odd\" println }}
+");
+            i += 1;
+        }
+        code
+    }
+
+    // run with `cargo test --release -- --ignored --nocapture generator_benchmark`.
+    // generator borrows tokens of bodies and scopes of binds, so its time should grow linearly with the size of the code
+    #[test]
+    #[ignore]
+    fn generator_benchmark () {
+        for lines in [100, 1000, 10000, 100000] {
+            let tokens = lex(program(lines), vec![]);
+            let time = Instant::now();
+            let statements = get_statements(&tokens, &mut Binds::new()).unwrap().stmts.len();
+            println!("generator: {lines} lines, {} tokens, {statements} statements in {:?}", tokens.len(), time.elapsed());
         }
     }
-}
\ No newline at end of file
+}
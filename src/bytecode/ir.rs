@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+/* Intermediate representation of the program, which the generator produces (see `gen.rs`) and the transpiler and
+the interpreter consume. Statements run for their effects, expressions compute values. Every body is a scope:
+variables declared in it with `let` aren't visible after it. Every node has span of the code it's generated from */
+
+// where the code of a node starts: file (empty for the compiled one), line and column
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span {
+    pub loc: Rc<str>,
+    pub line: usize,
+    pub col: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Body {
+    pub stmts: Vec<Stmt>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    // value is pushed to the stack, or it's the result of the function or the block
+    Expr(Expr),
+    // declares variable in the current scope. reference to variable `x &` is shared, not copied. `_` drops the value
+    Let(String, Expr),
+    // changes variable declared before
+    Assign(String, Expr),
+    // collection, index and value. collection given as variable is changed itself
+    Set(Expr, Expr, Expr),
+    Push(Expr, Expr),
+    // branches with their conditions and `else` branch
+    If(Vec<(Expr, Body)>, Option<Body>),
+    Loop(Body), Break, Continue,
+    Block(Body),
+    Println(Expr),
+    // name, arguments and body. functions can be called before their definition
+    Fn(String, Vec<String>, Body),
+    // declares variable with value popped from the runtime stack, `_` drops the value
+    Mov(String),
+    // stack shuffling word executed at runtime
+    StackOp(String),
+    // quotation and values pushed before calling it
+    CallQuote(Expr, Vec<Expr>),
+    // higher-order array word with array, initial value and quotation
+    ArrayOp(String, Vec<Expr>),
+    // parts of rust code: strings are code, variables are their names, references to strings are string literals
+    Rust(Vec<Expr>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Number(f64), String(String), Boolean(bool), Undefined,
+    Get(String),
+    // reference to the variable itself for `x &`, to the new value for other values
+    Ref(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Call(String, Vec<Expr>),
+    // values left by the body on its own stack
+    Array(Body),
+    // keys and values
+    Dict(Body, Body),
+    Pick(Box<Expr>, Box<Expr>),
+    Type(Box<Expr>),
+    // word implemented by runtime helper, with its operands
+    Builtin(String, Vec<Expr>),
+    // the first value of the body
+    Block(Body),
+    Quote(Body),
+    Curry(Box<Expr>, Box<Expr>), Compose(Box<Expr>, Box<Expr>),
+    // the same as `StmtKind::Rust`, but the code gives a value
+    Rust(Vec<Expr>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add, Sub, Mul, Div, Rem, Shl, Shr, Pow,
+    Eq, Ne, Lt, Gt, Le, Ge, And, Or
+}
+
+const OPS: [(BinOp, &str); 16] = [
+    (BinOp::Add, "+"), (BinOp::Sub, "-"), (BinOp::Mul, "*"), (BinOp::Div, "/"), (BinOp::Rem, "%"), (BinOp::Shl, "<<"), (BinOp::Shr, ">>"), (BinOp::Pow, "**"),
+    (BinOp::Eq, "="), (BinOp::Ne, "!="), (BinOp::Lt, "<"), (BinOp::Gt, ">"), (BinOp::Le, "<="), (BinOp::Ge, ">="), (BinOp::And, "&&"), (BinOp::Or, "||")
+];
+
+impl BinOp {
+
+    pub fn parse (symbol: &str) -> Option<BinOp> {
+        OPS.iter().find(|x| x.1 == symbol).map(|x| x.0)
+    }
+
+    pub fn symbol (self) -> &'static str {
+        OPS.iter().find(|x| x.0 == self).unwrap().1
+    }
+
+    // number of the operator in binary bytecode
+    pub fn code (self) -> u8 {
+        OPS.iter().position(|x| x.0 == self).unwrap() as u8
+    }
+
+    pub fn from_code (code: u8) -> Option<BinOp> {
+        OPS.get(code as usize).map(|x| x.0)
+    }
+
+    // result is boolean
+    pub fn is_logical (self) -> bool {
+        !matches!(self, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem | BinOp::Shl | BinOp::Shr | BinOp::Pow)
+    }
+
+}
+
+impl Expr {
+
+    pub fn new (kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+
+    // value known at compile time
+    pub fn is_static (&self) -> bool {
+        match &self.kind {
+            ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Undefined => true,
+            ExprKind::Array(x) | ExprKind::Block(x) => x.is_static(),
+            _ => false
+        }
+    }
+
+    // expressions and bodies nested in this one
+    pub fn children (&self) -> (Vec<&Expr>, Vec<&Body>) {
+        match &self.kind {
+            ExprKind::Ref(x) | ExprKind::Not(x) | ExprKind::Type(x) => (vec![&**x], vec![]),
+            ExprKind::Binary(_, a, b) | ExprKind::Pick(a, b) | ExprKind::Curry(a, b) | ExprKind::Compose(a, b) => (vec![&**a, &**b], vec![]),
+            ExprKind::Call(_, v) | ExprKind::Builtin(_, v) | ExprKind::Rust(v) => (v.iter().collect(), vec![]),
+            ExprKind::Array(x) | ExprKind::Block(x) | ExprKind::Quote(x) => (vec![], vec![x]),
+            ExprKind::Dict(k, v) => (vec![], vec![k, v]),
+            _ => (vec![], vec![])
+        }
+    }
+
+    pub fn children_mut (&mut self) -> (Vec<&mut Expr>, Vec<&mut Body>) {
+        match &mut self.kind {
+            ExprKind::Ref(x) | ExprKind::Not(x) | ExprKind::Type(x) => (vec![&mut **x], vec![]),
+            ExprKind::Binary(_, a, b) | ExprKind::Pick(a, b) | ExprKind::Curry(a, b) | ExprKind::Compose(a, b) => (vec![&mut **a, &mut **b], vec![]),
+            ExprKind::Call(_, v) | ExprKind::Builtin(_, v) | ExprKind::Rust(v) => (v.iter_mut().collect(), vec![]),
+            ExprKind::Array(x) | ExprKind::Block(x) | ExprKind::Quote(x) => (vec![], vec![x]),
+            ExprKind::Dict(k, v) => (vec![], vec![k, v]),
+            _ => (vec![], vec![])
+        }
+    }
+
+}
+
+impl Stmt {
+
+    pub fn new (kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+
+    // expressions and bodies nested in this statement
+    pub fn children (&self) -> (Vec<&Expr>, Vec<&Body>) {
+        match &self.kind {
+            StmtKind::Expr(x) | StmtKind::Let(_, x) | StmtKind::Assign(_, x) | StmtKind::Println(x) => (vec![x], vec![]),
+            StmtKind::Set(a, b, c) => (vec![a, b, c], vec![]),
+            StmtKind::Push(a, b) => (vec![a, b], vec![]),
+            StmtKind::If(branches, other) => (branches.iter().map(|x| &x.0).collect(), branches.iter().map(|x| &x.1).chain(other).collect()),
+            StmtKind::Loop(x) | StmtKind::Block(x) | StmtKind::Fn(_, _, x) => (vec![], vec![x]),
+            StmtKind::CallQuote(x, v) => (std::iter::once(x).chain(v).collect(), vec![]),
+            StmtKind::ArrayOp(_, v) | StmtKind::Rust(v) => (v.iter().collect(), vec![]),
+            _ => (vec![], vec![])
+        }
+    }
+
+    pub fn children_mut (&mut self) -> (Vec<&mut Expr>, Vec<&mut Body>) {
+        match &mut self.kind {
+            StmtKind::Expr(x) | StmtKind::Let(_, x) | StmtKind::Assign(_, x) | StmtKind::Println(x) => (vec![x], vec![]),
+            StmtKind::Set(a, b, c) => (vec![a, b, c], vec![]),
+            StmtKind::Push(a, b) => (vec![a, b], vec![]),
+            StmtKind::If(branches, other) => {
+                let (mut exprs, mut bodies): (Vec<&mut Expr>, Vec<&mut Body>) = (vec![], vec![]);
+                for (x, body) in branches { exprs.push(x); bodies.push(body); }
+                bodies.extend(other);
+                (exprs, bodies)
+            }
+            StmtKind::Loop(x) | StmtKind::Block(x) | StmtKind::Fn(_, _, x) => (vec![], vec![x]),
+            StmtKind::CallQuote(x, v) => (std::iter::once(x).chain(v).collect(), vec![]),
+            StmtKind::ArrayOp(_, v) | StmtKind::Rust(v) => (v.iter_mut().collect(), vec![]),
+            _ => (vec![], vec![])
+        }
+    }
+
+}
+
+impl Body {
+
+    // body of values known at compile time, like `[ 1 2 3 ]`
+    pub fn is_static (&self) -> bool {
+        self.stmts.iter().all(|x| matches!(&x.kind, StmtKind::Expr(x) if x.is_static()))
+    }
+
+}
@@ -0,0 +1,103 @@
+use crate::lexer::Token;
+use crate::diagnostic::Diagnostic;
+use super::{ir::{Body, Expr, ExprKind, Stmt, StmtKind}, value::Value};
+
+/* Compile-time stack of the generator. Values known at compile time are kept as values, so operations with them are
+folded, quotations known at compile time keep their tokens, so they can be expanded where they are called. Everything
+else is already the IR. Statements are on the stack too: values are taken from below them */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Const(Value),
+    // tokens of `do {...}`, and its body if it's valid as runtime code
+    Quote(Vec<Token>, Result<Body, Vec<Diagnostic>>),
+    // array literal with items which aren't all known at compile time
+    Array(Vec<Item>),
+    // `{...}`, its value is the last one
+    Block(Vec<Item>),
+    Expr(Expr),
+    Stmt(Stmt),
+    // `else if` continuing if-statement before it, as if-statement of its own
+    ElseIf(Stmt),
+    // statement leaving values on the runtime stack, and variables popped from it after the statement
+    Pops(Option<Stmt>, Vec<String>),
+    // if-statement decided at compile time: its body is taken, or skipped
+    PassedIf, FailedIf
+}
+
+impl Item {
+
+    // array literal, which is known at compile time when all its items are
+    pub fn array (items: Vec<Item>) -> Item {
+        match items.iter().map(|x| match x { Item::Const(x) => Some(x.clone()), _ => None }).collect() {
+            Some(x) => Item::Const(Value::Array(x)),
+            None => Item::Array(items)
+        }
+    }
+
+    pub fn expr (kind: ExprKind) -> Item {
+        Item::Expr(Expr::new(kind, Default::default()))
+    }
+
+    pub fn stmt (kind: StmtKind) -> Item {
+        Item::Stmt(Stmt::new(kind, Default::default()))
+    }
+
+    pub fn is_static (&self) -> bool {
+        match self {
+            Item::Const(_) => true,
+            Item::Array(v) | Item::Block(v) => v.iter().all(|x| x.is_static()),
+            _ => false
+        }
+    }
+
+    // instruction produces a value, so it can be taken by other instructions
+    pub fn is_value (&self) -> bool {
+        matches!(self, Item::Const(_) | Item::Quote(..) | Item::Array(_) | Item::Block(_) | Item::Expr(_))
+    }
+
+    // value can be evaluated any number of times and in any order
+    pub fn is_pure (&self) -> bool {
+        match self {
+            Item::Const(_) | Item::Quote(..) => true,
+            Item::Array(v) => v.iter().all(|x| x.is_pure()),
+            Item::Expr(x) => is_pure(x),
+            _ => false
+        }
+    }
+
+    // value known at compile time. block of static values is its last value
+    pub fn constant (&self) -> Option<Value> {
+        match self {
+            Item::Const(x) => Some(x.clone()),
+            Item::Array(v) => Some(Value::Array(v.iter().map(|x| x.constant()).collect::<Option<Vec<Value>>>()?)),
+            Item::Block(v) if self.is_static() => v.last()?.constant(),
+            _ => None
+        }
+    }
+
+    // items of array literal
+    pub fn items (self) -> Option<Vec<Item>> {
+        match self {
+            Item::Const(Value::Array(x)) => Some(x.into_iter().map(Item::Const).collect()),
+            Item::Array(x) => Some(x),
+            _ => None
+        }
+    }
+
+}
+
+fn is_pure (x: &Expr) -> bool {
+    match &x.kind {
+        ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Boolean(_) | ExprKind::Undefined | ExprKind::Get(_) | ExprKind::Quote(_) => true,
+        ExprKind::Array(body) => is_pure_body(body),
+        ExprKind::Dict(k, v) => is_pure_body(k) && is_pure_body(v),
+        ExprKind::Binary(_, a, b) | ExprKind::Pick(a, b) | ExprKind::Curry(a, b) | ExprKind::Compose(a, b) => is_pure(a) && is_pure(b),
+        ExprKind::Not(x) | ExprKind::Type(x) => is_pure(x),
+        ExprKind::Ref(x) => matches!(x.kind, ExprKind::Get(_)),
+        _ => false
+    }
+}
+
+fn is_pure_body (x: &Body) -> bool {
+    x.stmts.iter().all(|x| matches!(&x.kind, StmtKind::Expr(x) if is_pure(x)))
+}
@@ -1,5 +1,11 @@
 pub mod value;
+pub mod item;
 pub mod gen;
+pub mod binds;
+pub mod ir;
+pub mod text;
+pub mod altc;
+mod scope;
 mod parse;
 mod ops;
-mod display;
\ No newline at end of file
+mod display;
@@ -1,137 +1,204 @@
-use std::ops::{Add, Index, Sub};
+use crate::alt::value::Value as Data;
 use super::value::Value;
 
-/* This is implimentation of operators for static values. It's needed to do stuff at compile-time.
-Maybe it's too bloated, but it is what it is, lol. (literally cutted copy of module for Rust target) */
-
-macro_rules! arr_op {
-    ($a:expr, $x:tt, $b:expr) => {
-        let mut res: Vec<Value> = vec![];
-        let mut i = 0;
-        while i < $a.len() { if $b.len() > i { res.push($a[i].clone() $x $b[i].clone()); } else { res.push($a[i].clone()); } i += 1; }
-        if $b.len() > $a.len() { res.append(&mut $b[$a.len()..].to_vec()) }
-        return Value::Array(res);
-    };
+/* Values known at compile time are computed by the runtime itself (`cmp/src/alt`): static value is converted to
+runtime value, operator of the runtime is applied and the result is converted back. So folded code gives exactly
+the same result as generated code and interpreter, and semantics of values are written only once. */
+
+// runtime value of a value known at compile time
+pub fn to_runtime (x: &Value) -> Data {
+    match x {
+        Value::Number(x) => Data::Number(*x),
+        Value::String(x) => Data::String(x.clone()),
+        Value::Boolean(x) => Data::Boolean(*x),
+        Value::Undefined => Data::Undefined,
+        Value::Array(x) => Data::Arr(x.iter().map(to_runtime).collect())
+    }
 }
 
-macro_rules! join_arr {
-    ($sep:expr, $arr:expr) => {
-        return Value::String($arr.iter().map(|i| format!("{i}")).collect::<Vec<String>>().join($sep));
-    };
+// value known at compile time for a runtime value, `None` for values which can't be written as literals
+pub fn from_runtime (x: Data) -> Option<Value> {
+    match x {
+        Data::Number(x) => Some(Value::Number(x)),
+        Data::String(x) => Some(Value::String(x)),
+        Data::Boolean(x) => Some(Value::Boolean(x)),
+        Data::Undefined => Some(Value::Undefined),
+        Data::Arr(x) => Some(Value::Array(x.into_iter().map(from_runtime).collect::<Option<Vec<Value>>>()?)),
+        _ => None
+    }
 }
 
-impl Add for Value {
-    type Output = Value;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        match &self {
-            Value::Array(a) => {
-                match rhs {
-                    Value::Array(b) => { arr_op!(a, +, b); },
-                    Value::Number(b) => { return Value::Number(a.len() as f64 + b) }
-                    Value::String(b) => { join_arr!(&b, a); }
-                    _ => {}
-                }
-            }
-            Value::Number(a) => {
-                match rhs {
-                    Value::Number(b) => { return Value::Number(a + b) }
-                    Value::Array(b) => { return Value::Number(a + b.len() as f64) }
-                    Value::String(b) => { return Value::Number(a + b.len() as f64) }
-                    _ => {}
-                }
-            }
-            Value::String(a) => {
-                match rhs {
-                    Value::Number(b) => { return Value::Number(a.len() as f64 + b) }
-                    Value::String(b) => { return Value::String(format!("{a}{b}")) }
-                    Value::Array(b) => { join_arr!(&a, b); }
-                    _ => {}
-                }
-            }
-            _ => {}
+// applies function of the runtime to static values
+fn apply (args: &[&Value], f: impl FnOnce(Vec<Data>) -> Data) -> Option<Value> {
+    from_runtime(f(args.iter().map(|x| to_runtime(x)).collect()))
+}
+
+// `a b op`
+pub fn binary (a: &Value, b: &Value, op: &str) -> Option<Value> {
+    apply(&[a, b], |mut x| {
+        let (b, a) = (x.pop().unwrap(), x.pop().unwrap());
+        match op {
+            "+" => a + b, "-" => a - b, "*" => a * b, "/" => a / b, "%" => a % b, "<<" => a << b, ">>" => a >> b, "**" => a.pow(b),
+            "=" => Data::Boolean(a == b), "!=" => Data::Boolean(a != b),
+            "<" => Data::Boolean(a.cast_float() < b.cast_float()), ">" => Data::Boolean(a.cast_float() > b.cast_float()),
+            "<=" => Data::Boolean(a.cast_float() <= b.cast_float()), ">=" => Data::Boolean(a.cast_float() >= b.cast_float()),
+            "&&" => Data::Boolean(a.cast_bool() && b.cast_bool()), "||" => Data::Boolean(a.cast_bool() || b.cast_bool()),
+            _ => Data::Empty
         }
-        Value::NumOp(Box::new(self), Box::new(rhs), String::from("+"))
-    }
+    })
+}
+
+// `arr index pick`
+pub fn pick (arr: &Value, index: &Value) -> Option<Value> {
+    apply(&[arr, index], |x| x[0][x[1].clone()].clone())
 }
 
-impl Sub for Value {
-    type Output = Value;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        match &self {
-            Value::Array(a) => {
-                match rhs {
-                    Value::Array(b) => { arr_op!(a, -, b); },
-                    Value::Number(b) => { return Value::Number(a.len() as f64 - b) }
-                    _ => {}
-                }
+// words implemented by helpers of the runtime (`cmp/src/alt/value.rs`), for static values. `None` when the value isn't known at compile time
+impl Value {
+
+    pub fn cast_bool_static (&self) -> bool {
+        to_runtime(self).cast_bool()
+    }
+
+    pub fn not_static (self) -> Option<Value> {
+        apply(&[&self], |x| Data::Boolean(!x[0].cast_bool()))
+    }
+
+    // `sum`, `flat`, `inc`, `dec`, `int` and `array`
+    pub fn word_static (self, word: &str) -> Option<Value> {
+        apply(&[&self], |mut x| {
+            let mut a = x.remove(0);
+            match word {
+                "sum" => a.sum(),
+                "flat" => a.flat(),
+                "inc" => { a.inc(); a }
+                "dec" => { a.dec(); a }
+                "int" => Data::Number(a.cast_int() as f64),
+                _ => Data::Arr(a.cast_vec())
             }
-            Value::Number(a) => {
-                match rhs {
-                    Value::Number(b) => { return Value::Number(a - b) }
-                    Value::Array(b) => { return Value::Number(a - b.len() as f64) }
-                    Value::String(b) => { return Value::Number(a - b.len() as f64) }
-                    _ => {}
-                }
+        })
+    }
+
+    pub fn concat_static (self, b: Value) -> Option<Value> {
+        apply(&[&self, &b], |mut x| { let b = x.pop().unwrap(); x.pop().unwrap().concat(b) })
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::remove_dir_all, process::Command};
+    use crate::{alt::value::Value as Data, interpreter::Interpreter, runtime::{materialize, temp_build_dir}, transpiler::transpile};
+    use super::{super::ir::{BinOp, Body, Expr, ExprKind, Stmt, StmtKind}, to_runtime, Value};
+
+    const BINARY: &[&str] = &["+", "-", "*", "/", "%", "<<", ">>", "**", "=", "!=", "<", ">", "<=", ">=", "&&", "||", "pick", "concat"];
+    const UNARY: &[&str] = &["not", "sum", "flat", "inc", "dec", "int", "array"];
+
+    fn samples () -> Vec<Value> {
+        let s = |x: &str| Value::String(x.to_string());
+        vec![
+            Value::Number(0.0), Value::Number(2.0), Value::Number(-1.5),
+            s(""), s("a,b"), s(","),
+            Value::Boolean(true), Value::Boolean(false),
+            Value::Array(vec![]), Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]), Value::Array(vec![s(","), Value::Array(vec![Value::Number(3.0)])]),
+            Value::Undefined
+        ]
+    }
+
+    // instruction applying the operator to its operands
+    fn op (op: &str, args: Vec<Expr>) -> Expr {
+        let mut args = args.into_iter();
+        let mut next = || Box::new(args.next().unwrap());
+        let kind = match op {
+            "not" => ExprKind::Not(next()),
+            "pick" => ExprKind::Pick(next(), next()),
+            "sum" | "flat" | "inc" | "dec" | "int" | "array" | "concat" => ExprKind::Builtin(op.to_string(), args.collect()),
+            _ => ExprKind::Binary(BinOp::parse(op).unwrap(), next(), next())
+        };
+        expr(kind)
+    }
+
+    fn expr (kind: ExprKind) -> Expr {
+        Expr::new(kind, Default::default())
+    }
+
+    fn stmt (kind: StmtKind) -> Stmt {
+        Stmt::new(kind, Default::default())
+    }
+
+    // every operator with every combination of operands
+    fn cases () -> Vec<(&'static str, Vec<Value>)> {
+        let mut cases = vec![];
+        for x in BINARY { for a in samples() { for b in samples() { cases.push((*x, vec![a.clone(), b])); } } }
+        for x in UNARY { for a in samples() { cases.push((*x, vec![a])); } }
+        cases
+    }
+
+    // result computed at compile time
+    fn fold (op: &str, args: &[Value]) -> Option<Data> {
+        let folded = match op {
+            "pick" => super::pick(&args[0], &args[1]),
+            "concat" => args[0].clone().concat_static(args[1].clone()),
+            "not" => args[0].clone().not_static(),
+            "sum" | "flat" | "inc" | "dec" | "int" | "array" => args[0].clone().word_static(op),
+            _ => super::binary(&args[0], &args[1], op)
+        };
+        Some(to_runtime(&folded?))
+    }
+
+    // operands are put into variables, or passed to a function which gets references to them, so nothing is known at compile time
+    fn program (cases: &[(&str, Vec<Value>)], f: impl Fn(Expr) -> Stmt, function: bool) -> Vec<Stmt> {
+        let mut program = vec![];
+        for (i, (x, args)) in cases.iter().enumerate() {
+            let names: Vec<String> = (0..args.len()).map(|j| format!("x{i}_{j}")).collect();
+            if function {
+                let operands = names.iter().map(|x| expr(ExprKind::Ref(Box::new(expr(ExprKind::Get(x.clone())))))).collect();
+                program.push(stmt(StmtKind::Fn(format!("f{i}"), names, Body { stmts: vec![stmt(StmtKind::Expr(op(x, operands)))] })));
+                program.push(f(expr(ExprKind::Call(format!("f{i}"), args.iter().map(|x| x.clone().expr()).collect()))));
+                continue
             }
-            Value::String(a) => {
-                match rhs {
-                    Value::Number(b) => { return Value::Number(a.len() as f64 - b) }
-                    Value::String(b) => { return Value::String(a.replace(&b, "")) }
-                    _ => {}
-                }
+            program.extend(names.iter().zip(args).map(|(name, x)| stmt(StmtKind::Let(name.clone(), x.clone().expr()))));
+            program.push(f(op(x, names.into_iter().map(|x| expr(ExprKind::Get(x))).collect())));
+        }
+        program
+    }
+
+    #[test]
+    fn folding_matches_interpreter () {
+        let cases = cases();
+        for function in [false, true] {
+            let mut interpreter = Interpreter::new();
+            interpreter.run(&Body { stmts: program(&cases, |x| stmt(StmtKind::Expr(x)), function) }).unwrap();
+            assert_eq!(interpreter.stack.len(), cases.len());
+
+            for ((x, args), result) in cases.iter().zip(interpreter.stack) {
+                let folded = fold(x, args).unwrap_or_else(|| panic!("`{x}` isn't folded for {args:?}"));
+                assert_eq!(format!("{folded:?}"), format!("{result:?}"), "`{x}` of {args:?}, in function: {function}");
             }
-            _ => {}
         }
-        Value::NumOp(Box::new(self), Box::new(rhs), String::from("-"))
     }
-}
 
+    #[test]
+    fn folding_matches_generated_code () {
+        let cases = cases();
+        let print = |x| stmt(StmtKind::Println(expr(ExprKind::Array(Body { stmts: vec![stmt(StmtKind::Expr(x)), stmt(StmtKind::Expr(expr(ExprKind::String("|".to_string()))))] }))));
+        let program = [program(&cases, print, false), program(&cases, print, true)].concat();
+        let rust = transpile(Body { stmts: program }, &mut HashMap::new());
 
-impl Index<Value> for Value {
-    type Output = Value;
-
-    fn index(&self, index: Value) -> &Self::Output {
-        match self {
-            Value::Array(x) => {
-                match index {
-                    Value::Number(y) => {
-                        let mut y = y as isize;
-                        if y < 0 { y = x.len() as isize + y; }
-                        if y >= x.len() as isize { return &Value::Undefined }
-                        return &x[y as usize]
-                    }
-                    Value::Array(y) => {
-                        let mut result = vec![];
-                        for i in y { result.push(self[i].clone()); }
-                        return Box::leak(Box::new(Value::Array(result)))
-                    }
-                    _ => {}
-                }
-            },
-            Value::String(x) => {
-                let x: Vec<char> = x.chars().collect();
-                match index {
-                    Value::Number(y) => {
-                        let mut y = y as isize;
-                        if y < 0 { y = x.len() as isize + y; }
-                        if y >= x.len() as isize { return &Value::Undefined }
-                        return Box::leak(Box::new(Value::String(x[y as usize].to_string())))
-                    },
-                    Value::Array(y) => {
-                        let mut result = vec![];
-                        for i in y { result.push(self[i].clone()); }
-                        return Box::leak(Box::new(Value::Array(result)))
-                    }
-                    _ => {}
-                }
-            },
-            
-            _ => {}
+        let dir = temp_build_dir();
+        materialize(&dir, &rust).unwrap();
+        let built = Command::new("rustc").current_dir(&dir).args(["main.rs", "-C", "opt-level=0", "-o", "program"]).output().unwrap();
+        assert!(built.status.success(), "{}", String::from_utf8_lossy(&built.stderr));
+        let output = Command::new(dir.join("program")).output().unwrap();
+        let _ = remove_dir_all(&dir);
+
+        let output = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        // and the stack, printed at the end
+        assert_eq!(lines.len(), cases.len() * 2 + 1);
+        for ((x, args), line) in cases.iter().chain(&cases).zip(lines) {
+            let folded = fold(x, args).unwrap();
+            assert_eq!(format!("{}", Data::Arr(vec![folded, Data::String("|".to_string())])), line, "`{x}` of {args:?}");
         }
-        &Value::Undefined
     }
 }
-
-// new ops coming soon...
\ No newline at end of file
@@ -1,43 +1,118 @@
+use std::{borrow::Cow, ops::Index};
+
 use crate::lexer::{TokenKind, Token};
+use crate::diagnostic::{Diagnostic, Span};
+
+/* Tokens being generated: slices of the code and of expanded macros, each one continues the previous one.
+Instructions take their tokens without copying them, only a body crossing the end of a macro is collected */
+#[derive(Clone, Debug)]
+pub struct Tokens<'a> {
+    parts: Vec<&'a [Token]>
+}
+
+impl<'a> Tokens<'a> {
+
+    pub fn new (tokens: &'a [Token]) -> Self {
+        Tokens { parts: vec![tokens] }
+    }
+
+    // `first` followed by `rest`
+    pub fn then (first: &'a [Token], rest: &Tokens<'a>) -> Self {
+        let mut parts = vec![first];
+        parts.extend(rest.parts.iter().copied());
+        Tokens { parts }
+    }
+
+    pub fn get (&self, mut index: usize) -> Option<&'a Token> {
+        for x in &self.parts {
+            if index < x.len() { return Some(&x[index]) }
+            index -= x.len();
+        }
+        None
+    }
+
+    pub fn iter (&self) -> impl Iterator<Item = &'a Token> + '_ {
+        self.parts.iter().flat_map(|x| x.iter())
+    }
 
-pub fn parse_pair_symbols (tokens: &Vec<Token>, pair: (&str, &str)) -> Option<(Vec<Token>, usize)> {
+    // tokens without the first `n`
+    pub fn skip (&self, mut n: usize) -> Tokens<'a> {
+        let mut parts = vec![];
+        for x in &self.parts {
+            if n >= x.len() { n -= x.len(); continue }
+            parts.push(&x[n..]);
+            n = 0;
+        }
+        Tokens { parts }
+    }
+
+    // tokens from `start` to `end`, they are copied only when they are in several parts
+    pub fn range (&self, start: usize, end: usize) -> Cow<'a, [Token]> {
+        let rest = self.skip(start);
+        match rest.parts.first() {
+            Some(x) if x.len() >= end - start => Cow::Borrowed(&x[..end - start]),
+            _ => Cow::Owned(rest.iter().take(end - start).cloned().collect())
+        }
+    }
+
+}
+
+impl Index<usize> for Tokens<'_> {
+    type Output = Token;
+
+    fn index (&self, index: usize) -> &Token {
+        self.get(index).expect("token index out of bounds")
+    }
+}
+
+pub fn parse_pair_symbols<'a> (tokens: &Tokens<'a>, pair: (&str, &str)) -> Option<(Cow<'a, [Token]>, usize)> {
     let mut pair_joined = 0;
-    let mut children: Vec<Token> = vec![];
 
-    if tokens[0].value == pair.0.to_string() {
+    if tokens.get(0)?.value == pair.0 {
         pair_joined += 1;
     }
     else { return None }
 
-    let mut token = 1;
-    while token < tokens.len() {
-        if tokens[token].value == pair.0.to_string() {
+    for (token, x) in tokens.iter().enumerate().skip(1) {
+        if x.value == pair.0 {
             pair_joined += 1;
         }
 
-        if tokens[token].value == pair.1.to_string() && pair_joined > 0 {
+        if x.value == pair.1 {
             if pair_joined == 1 {
-                return Some((children, token+1))
+                return Some((tokens.range(1, token), token+1))
             }
             else {
                 pair_joined -= 1;
             }
         }
+    }
 
-        if pair_joined > 0 {
-            children.push(tokens[token].clone());
+    None
+}
+
+// same as `parse_pair_symbols`, but body starts at `start` and token before it is the one that requires the body.
+// returned length counts tokens from the beginning of `tokens`
+pub fn parse_body<'a> (tokens: &Tokens<'a>, start: usize, pair: (&str, &str)) -> Result<(Cow<'a, [Token]>, usize), Diagnostic> {
+    let owner = &tokens[start.saturating_sub(1)];
+    match tokens.get(start) {
+        Some(open) if open.value == pair.0 => {
+            match parse_pair_symbols(&tokens.skip(start), pair) {
+                Some((body, len)) => Ok((body, start + len)),
+                None => Err(Diagnostic::error(open, format!("unclosed `{}`", pair.0))
+                    .note(format!("body of `{}` starts here and never ends", owner.value))
+                    .help(format!("add `{}` to close the body", pair.1)))
+            }
         }
-    
-        token += 1;
+        Some(x) => Err(Diagnostic::error(x, format!("expected `{}` after `{}`, found `{}`", pair.0, owner.value, x.value))),
+        None => Err(Diagnostic::error(owner, format!("expected `{}` after `{}`", pair.0, owner.value)))
     }
-    
-    None
 }
 
-pub fn parse_args (tokens: &Vec<Token>, start: usize) -> (Vec<String>, usize) {
+pub fn parse_args (tokens: &Tokens, start: usize) -> (Vec<String>, usize) {
     let mut args: Vec<String> = vec![];
     let mut last = start;
-    for i in &tokens[start..] {
+    for i in tokens.iter().skip(start) {
         if let TokenKind::Keyword = i.typ { last += 1; args.push(i.value.clone()); }
         else { break; }
     }
@@ -45,14 +120,99 @@ pub fn parse_args (tokens: &Vec<Token>, start: usize) -> (Vec<String>, usize) {
     (args, last)
 }
 
-pub fn parse_string (value: String) -> String {
-    let mut result;
+// all numbers are 64-bit floats, so literal must fit into it. Integers are exact up to 2^53
+pub fn parse_number (token: &Token) -> Result<f64, Diagnostic> {
+    let (negative, value) = match token.value.strip_prefix('-') { Some(x) => (true, x), None => (false, token.value.as_str()) };
+    let value = value.replace('_', "");
 
-    if value.starts_with("r") {
-        result = value[3..value.len()-2].to_string();
-        result = result.replace(r#"""#, r#"\""#);
+    let x = if token.typ == TokenKind::Int {
+        let (radix, digits) = match value.get(..2) {
+            Some("0x") => (16, &value[2..]), Some("0b") => (2, &value[2..]), Some("0o") => (8, &value[2..]),
+            _ => (10, value.as_str())
+        };
+        if digits.is_empty() { return Err(Diagnostic::error(token, format!("number literal `{}` has no digits", token.value))) }
+
+        let Ok(x) = u128::from_str_radix(digits, radix) else {
+            return Err(Diagnostic::error(token, format!("integer literal `{}` is too large", token.value))
+                .note("numbers are 64-bit floats, integers are exact only up to 2^53 (9007199254740992)"))
+        };
+        let f = x as f64;
+        if f as u128 != x {
+            return Err(Diagnostic::error(token, format!("integer literal `{}` cannot be represented exactly", token.value))
+                .note(format!("it would be rounded to {f}, integers are exact only up to 2^53 (9007199254740992)"))
+                .help(format!("write `{f:e}` if rounded value is fine")))
+        }
+        f
     }
-    else {result = value[1..value.len()-1].to_string();}
+    else {
+        let Ok(f) = value.parse::<f64>() else { return Err(Diagnostic::error(token, format!("invalid number literal `{}`", token.value))) };
+        if f.is_infinite() {
+            return Err(Diagnostic::error(token, format!("real literal `{}` is too large", token.value))
+                .note(format!("the largest number is {:e}", f64::MAX))
+                .help("use `inf` for infinity"))
+        }
+        let mantissa = value.split(['e', 'E']).next().unwrap_or_default();
+        if f == 0.0 && mantissa.chars().any(|x| x.is_ascii_digit() && x != '0') {
+            return Err(Diagnostic::error(token, format!("real literal `{}` is too small and would be rounded to zero", token.value))
+                .note(format!("the smallest positive number is {:e}", f64::MIN_POSITIVE * f64::EPSILON)))
+        }
+        f
+    };
+
+    Ok(if negative { -x } else { x })
+}
 
-    result
-}
\ No newline at end of file
+// strips quotes and processes escape sequences. Raw strings `r#"..."#` are taken as is
+pub fn parse_string (token: &Token) -> Result<String, Diagnostic> {
+    let value = &token.value;
+    if value.starts_with('r') { return Ok(value[3..value.len()-2].to_string()) }
+
+    let chars: Vec<char> = value.chars().collect();
+    let chars = &chars[1..chars.len()-1];
+    let mut result = String::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\\' { result.push(chars[i]); i += 1; continue }
+
+        let escape = chars.get(i+1).copied().unwrap_or('\\');
+        let len = match escape {
+            'n' => { result.push('\n'); 2 }
+            't' => { result.push('\t'); 2 }
+            'r' => { result.push('\r'); 2 }
+            '0' => { result.push('\0'); 2 }
+            '\\' | '"' | '\'' => { result.push(escape); 2 }
+            'u' => {
+                let end = chars[i..].iter().position(|x| *x == '}').map(|x| i + x);
+                let end = end.filter(|_| chars.get(i+2) == Some(&'{'));
+                let code = end.map(|end| chars[i+3..end].iter().collect::<String>()).unwrap_or_default();
+                let c = u32::from_str_radix(&code, 16).ok().filter(|_| (1..=6).contains(&code.len())).and_then(char::from_u32);
+                let Some(c) = c else {
+                    let len = end.map(|x| x - i + 1).unwrap_or(2);
+                    return Err(escape_error(token, chars, i, len, "invalid unicode escape")
+                        .help("unicode escapes look like `\\u{263A}`: up to 6 hex digits of a valid code point"))
+                };
+                result.push(c);
+                end.unwrap() - i + 1
+            }
+            x => {
+                return Err(escape_error(token, chars, i, 2, format!("unknown escape sequence `\\{x}`"))
+                    .note("supported escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` and `\\u{...}`")
+                    .help("use `\\\\` for a backslash or raw string `r#\"...\"#` to keep text as is"))
+            }
+        };
+        i += len;
+    }
+
+    Ok(result)
+}
+
+// points to the escape at `index` of string contents
+fn escape_error (token: &Token, chars: &[char], index: usize, len: usize, message: impl Into<String>) -> Diagnostic {
+    let (mut line, mut col) = (token.line, token.col + 1);
+    for c in &chars[..index] {
+        if *c == '\n' { line += 1; col = 1; } else { col += 1; }
+    }
+
+    Diagnostic::error(token, message).at(Span { line, col, len })
+}
@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+use super::ir::{Body, Expr, Stmt, StmtKind};
+
+/* Declarations of variables. The generator gives every assignment as `let`, which becomes change of the variable when
+it's declared before in a visible scope. Bodies of if-statements, loops, blocks and arrays are scopes, function sees
+only its arguments, quotation sees variables around it */
+
+// `declared` are variables declared before the body, like the ones of previous lines of the repl
+pub fn declare (body: &mut Body, declared: Vec<String>) {
+    let mut scopes = vec![declared.into_iter().collect()];
+    body.stmts.iter_mut().for_each(|x| stmt(x, &mut scopes));
+}
+
+fn scoped (body: &mut Body, scopes: &mut Vec<HashSet<String>>) {
+    scopes.push(HashSet::new());
+    body.stmts.iter_mut().for_each(|x| stmt(x, scopes));
+    scopes.pop();
+}
+
+fn stmt (x: &mut Stmt, scopes: &mut Vec<HashSet<String>>) {
+    if let StmtKind::Fn(_, args, body) = &mut x.kind {
+        scoped(body, &mut vec![args.iter().cloned().collect()]);
+        return
+    }
+    let (exprs, bodies) = x.children_mut();
+    exprs.into_iter().for_each(|x| expr(x, scopes));
+    bodies.into_iter().for_each(|x| scoped(x, scopes));
+
+    match &x.kind {
+        StmtKind::Let(name, _) if scopes.iter().any(|x| x.contains(name)) => {
+            let StmtKind::Let(name, value) = std::mem::replace(&mut x.kind, StmtKind::Break) else { unreachable!() };
+            x.kind = StmtKind::Assign(name, value);
+        }
+        StmtKind::Let(name, _) | StmtKind::Mov(name) if name != "_" => { scopes.last_mut().unwrap().insert(name.clone()); }
+        _ => {}
+    }
+}
+
+fn expr (x: &mut Expr, scopes: &mut Vec<HashSet<String>>) {
+    let (exprs, bodies) = x.children_mut();
+    exprs.into_iter().for_each(|x| expr(x, scopes));
+    bodies.into_iter().for_each(|x| scoped(x, scopes));
+}
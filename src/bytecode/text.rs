@@ -0,0 +1,610 @@
+use std::rc::Rc;
+use crate::diagnostic::{self, Diagnostic, Severity};
+use super::ir::{BinOp, Body, Expr, ExprKind, Span, Stmt, StmtKind};
+
+/* Textual form of the IR, written by `alt dump-ir` and `:ir` of the repl and read back by `alt <command> file.ir`.
+Every statement is on its own line after its line and column, `# path` is written before the first statement from
+another file. Expressions are inline, with binary operators in parentheses. Span of an expression is written as
+`@line:col` only when it differs from the span of the node around it. Bodies of arrays and blocks are inline when
+they have only expressions from the same place. Names like words of the format are written as `r#name` */
+
+// words of the format, which can't be names
+const RESERVED: [&str; 23] = [
+    "let", "set", "push", "if", "else", "loop", "block", "break", "continue", "println", "fn", "mov", "stack", "call", "with", "array", "rust",
+    "true", "false", "undefined", "inf", "NaN", "do"
+];
+
+pub fn print (body: &Body) -> String {
+    let mut printer = Printer { text: String::new(), loc: "".into() };
+    printer.body(body, 0);
+    printer.text
+}
+
+pub fn parse (text: &str) -> Result<Body, Diagnostic> {
+    Parser { chars: text.chars().collect(), pos: 0, loc: "".into() }.body(None)
+}
+
+struct Printer {
+    text: String,
+    // file of the last written statement
+    loc: Rc<str>
+}
+
+impl Printer {
+
+    fn body (&mut self, body: &Body, depth: usize) {
+        for i in &body.stmts { self.stmt(i, depth); }
+    }
+
+    // opens line of the statement
+    fn start (&mut self, span: &Span, depth: usize) {
+        if span.loc != self.loc {
+            self.text += format!("# {}", span.loc).trim_end();
+            self.text += "\n";
+            self.loc = span.loc.clone();
+        }
+        self.text += &format!("{:<8}{}", format!("{}:{}", span.line, span.col), "    ".repeat(depth));
+    }
+
+    // body of a statement or multiline body of an expression, opening bracket is already written
+    fn block (&mut self, body: &Body, depth: usize, close: &str) {
+        self.text += "\n";
+        self.body(body, depth + 1);
+        self.text += &format!("{:8}{}{close}", "", "    ".repeat(depth));
+    }
+
+    fn stmt (&mut self, stmt: &Stmt, depth: usize) {
+        self.start(&stmt.span, depth);
+        let span = &stmt.span;
+        match &stmt.kind {
+            StmtKind::Expr(x) => self.expr(x, span, depth),
+            StmtKind::Let(name, x) => { self.text += &format!("let {} = ", word(name)); self.expr(x, span, depth); }
+            StmtKind::Assign(name, x) => { self.text += &format!("{} = ", word(name)); self.expr(x, span, depth); }
+            StmtKind::Set(arr, index, x) => {
+                self.text += "set ";
+                self.pick(arr, index, span, depth);
+                self.text += " = ";
+                self.expr(x, span, depth);
+            }
+            StmtKind::Push(arr, x) => { self.text += "push "; self.exprs([arr, x], span, depth); }
+            StmtKind::If(branches, other) => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    self.text += if i == 0 { "if " } else { " else if " };
+                    self.expr(condition, span, depth);
+                    self.text += " {";
+                    self.block(body, depth, "}");
+                }
+                if let Some(body) = other { self.text += " else {"; self.block(body, depth, "}"); }
+            }
+            StmtKind::Loop(body) => { self.text += "loop {"; self.block(body, depth, "}"); }
+            StmtKind::Block(body) => { self.text += "block {"; self.block(body, depth, "}"); }
+            StmtKind::Break => self.text += "break",
+            StmtKind::Continue => self.text += "continue",
+            StmtKind::Println(x) => { self.text += "println "; self.expr(x, span, depth); }
+            StmtKind::Fn(name, args, body) => {
+                self.text += &format!("fn {}({}) {{", word(name), args.iter().map(|x| word(x)).collect::<Vec<String>>().join(", "));
+                self.block(body, depth, "}");
+            }
+            StmtKind::Mov(name) => self.text += &format!("mov {}", word(name)),
+            StmtKind::StackOp(x) => self.text += &format!("stack {x}"),
+            StmtKind::CallQuote(x, args) => {
+                self.text += "call ";
+                self.expr(x, span, depth);
+                if !args.is_empty() { self.text += " with "; self.exprs(args, span, depth); }
+            }
+            StmtKind::ArrayOp(x, args) => { self.text += &format!("array {x}("); self.exprs(args, span, depth); self.text += ")"; }
+            StmtKind::Rust(parts) => { self.text += "rust("; self.exprs(parts, span, depth); self.text += ")"; }
+        }
+        self.text += "\n";
+    }
+
+    fn exprs<'a> (&mut self, x: impl IntoIterator<Item = &'a Expr>, span: &Span, depth: usize) {
+        for (i, x) in x.into_iter().enumerate() {
+            if i > 0 { self.text += ", "; }
+            self.expr(x, span, depth);
+        }
+    }
+
+    // `span` is the span of the node around the expression
+    fn expr (&mut self, expr: &Expr, span: &Span, depth: usize) {
+        if expr.span != *span {
+            let loc = if expr.span.loc == span.loc { String::new() } else { format!("{:?}:", expr.span.loc) };
+            self.text += &format!("@{loc}{}:{} ", expr.span.line, expr.span.col);
+        }
+        let span = &expr.span;
+        match &expr.kind {
+            ExprKind::Number(x) => self.text += &x.to_string(),
+            ExprKind::String(x) => self.text += &format!("{x:?}"),
+            ExprKind::Boolean(x) => self.text += &x.to_string(),
+            ExprKind::Undefined => self.text += "undefined",
+            ExprKind::Get(name) => self.text += &word(name),
+            ExprKind::Ref(x) => { self.text += "&"; self.expr(x, span, depth); }
+            ExprKind::Binary(op, a, b) => {
+                self.text += "(";
+                self.expr(a, span, depth);
+                self.text += &format!(" {} ", op.symbol());
+                self.expr(b, span, depth);
+                self.text += ")";
+            }
+            ExprKind::Not(x) => { self.text += "!"; self.expr(x, span, depth); }
+            ExprKind::Call(name, args) => { self.text += &format!("{}(", word(name)); self.exprs(args, span, depth); self.text += ")"; }
+            ExprKind::Array(body) => self.items(body, span, depth, "[", "]"),
+            ExprKind::Dict(k, v) => {
+                self.text += "dict!(";
+                self.items(k, span, depth, "[", "]");
+                self.text += ", ";
+                self.items(v, span, depth, "[", "]");
+                self.text += ")";
+            }
+            ExprKind::Pick(arr, index) => self.pick(arr, index, span, depth),
+            ExprKind::Type(x) => { self.text += "type!("; self.expr(x, span, depth); self.text += ")"; }
+            ExprKind::Builtin(x, args) => { self.text += &format!("{x}!("); self.exprs(args, span, depth); self.text += ")"; }
+            ExprKind::Block(body) => self.items(body, span, depth, "{", "}"),
+            ExprKind::Quote(body) => self.items(body, span, depth, "do {", "}"),
+            ExprKind::Curry(x, q) => { self.text += "curry!("; self.exprs([&**x, &**q], span, depth); self.text += ")"; }
+            ExprKind::Compose(p, q) => { self.text += "compose!("; self.exprs([&**p, &**q], span, depth); self.text += ")"; }
+            ExprKind::Rust(parts) => { self.text += "rust!("; self.exprs(parts, span, depth); self.text += ")"; }
+        }
+    }
+
+    // `arr[index]`, collection starting with `&`, `!` or span is in parentheses
+    fn pick (&mut self, arr: &Expr, index: &Expr, span: &Span, depth: usize) {
+        let grouped = matches!(arr.kind, ExprKind::Ref(_) | ExprKind::Not(_)) || arr.span != *span;
+        if grouped { self.text += "("; }
+        self.expr(arr, span, depth);
+        if grouped { self.text += ")"; }
+        self.text += "[";
+        self.expr(index, span, depth);
+        self.text += "]";
+    }
+
+    // body of an expression
+    fn items (&mut self, body: &Body, span: &Span, depth: usize, open: &str, close: &str) {
+        self.text += open;
+        if !inline(body, span) { return self.block(body, depth, close) }
+
+        for (i, x) in body.stmts.iter().enumerate() {
+            if i > 0 { self.text += ", "; }
+            if let StmtKind::Expr(x) = &x.kind { self.expr(x, span, depth); }
+        }
+        self.text += close;
+    }
+
+}
+
+// body has only expressions at `span`
+fn inline (body: &Body, span: &Span) -> bool {
+    body.stmts.iter().all(|x| match &x.kind {
+        StmtKind::Expr(e) => x.span == *span && e.span == *span && e.children().1.iter().all(|x| inline(x, span)),
+        _ => false
+    })
+}
+
+fn word (name: &str) -> String {
+    if RESERVED.contains(&name) || name.ends_with('!') { format!("r#{name}") } else { name.to_string() }
+}
+
+type Parsed<T> = Result<T, Diagnostic>;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    // file of the statements, set by `# path`
+    loc: Rc<str>
+}
+
+impl Parser {
+
+    fn at (&self, i: usize) -> Option<char> {
+        self.chars.get(self.pos + i).copied()
+    }
+
+    fn skip (&mut self) {
+        while self.at(0).is_some_and(|x| x.is_whitespace()) { self.pos += 1; }
+    }
+
+    // takes `s` if it's next after whitespace
+    fn eat (&mut self, s: &str) -> bool {
+        self.skip();
+        let found = s.chars().enumerate().all(|(i, c)| self.at(i) == Some(c));
+        if found { self.pos += s.chars().count(); }
+        found
+    }
+
+    fn expect (&mut self, s: &str) -> Parsed<()> {
+        if self.eat(s) { return Ok(()) }
+        Err(self.error(format!("expected `{s}`")))
+    }
+
+    fn error (&self, message: impl Into<String>) -> Diagnostic {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|x| **x == '\n').count() + 1;
+        let col = before.iter().rev().take_while(|x| **x != '\n').count() + 1;
+        Diagnostic { severity: Severity::Error, message: message.into(), span: diagnostic::Span { line, col, len: 1 }, loc: "".into(), notes: vec![], help: None }
+    }
+
+    // name or word of the format, and whether it's written as `r#name`
+    fn word (&mut self) -> (String, bool) {
+        self.skip();
+        let raw = self.at(0) == Some('r') && self.at(1) == Some('#');
+        if raw { self.pos += 2; }
+        let start = self.pos;
+        while self.at(0).is_some_and(|x| !x.is_whitespace() && !"()[]{},\"&@#=".contains(x)) { self.pos += 1; }
+        (self.chars[start..self.pos].iter().collect(), raw)
+    }
+
+    fn name (&mut self) -> Parsed<String> {
+        let (name, _) = self.word();
+        if name.is_empty() { return Err(self.error("expected a name")) }
+        Ok(name)
+    }
+
+    // takes word `x` if it's next
+    fn keyword (&mut self, x: &str) -> bool {
+        let start = self.pos;
+        let (word, raw) = self.word();
+        if !raw && word == x { return true }
+        self.pos = start;
+        false
+    }
+
+    fn integer (&mut self) -> Parsed<usize> {
+        let start = self.pos;
+        while self.at(0).is_some_and(|x| x.is_ascii_digit()) { self.pos += 1; }
+        self.chars[start..self.pos].iter().collect::<String>().parse().map_err(|_| self.error("expected a number"))
+    }
+
+    // `line:col` of a statement
+    fn span (&mut self) -> Parsed<Span> {
+        self.skip();
+        let line = self.integer()?;
+        if self.at(0) != Some(':') { return Err(self.error("expected `:` between line and column")) }
+        self.pos += 1;
+        Ok(Span { loc: self.loc.clone(), line, col: self.integer()? })
+    }
+
+    // statement is next: it starts with its span or file
+    fn at_stmt (&mut self) -> bool {
+        self.skip();
+        let digits = (0..).take_while(|i| self.at(*i).is_some_and(|x| x.is_ascii_digit())).count();
+        self.at(0) == Some('#') || digits > 0 && self.at(digits) == Some(':')
+    }
+
+    // statements until `close`, or until the end of the text
+    fn body (&mut self, close: Option<char>) -> Parsed<Body> {
+        let mut stmts = vec![];
+        loop {
+            self.skip();
+            match self.at(0) {
+                None if close.is_none() => break,
+                Some(x) if Some(x) == close => { self.pos += 1; break }
+                Some('#') => {
+                    let start = self.pos + 1;
+                    while self.at(0).is_some_and(|x| x != '\n') { self.pos += 1; }
+                    self.loc = self.chars[start..self.pos].iter().collect::<String>().trim().into();
+                }
+                _ if !self.at_stmt() => return Err(self.error(match close { Some(x) => format!("expected a statement or `{x}`"), None => "expected a statement".to_string() })),
+                _ => stmts.push(self.stmt()?)
+            }
+        }
+        Ok(Body { stmts })
+    }
+
+    // `{`, statements and `}`
+    fn braced (&mut self) -> Parsed<Body> {
+        self.expect("{")?;
+        self.body(Some('}'))
+    }
+
+    // expressions separated by commas until `close`
+    fn exprs (&mut self, close: &str, span: &Span) -> Parsed<Vec<Expr>> {
+        let mut x = vec![];
+        if self.eat(close) { return Ok(x) }
+        loop {
+            x.push(self.expr(span)?);
+            if self.eat(close) { return Ok(x) }
+            self.expect(",")?;
+        }
+    }
+
+    fn stmt (&mut self) -> Parsed<Stmt> {
+        let span = self.span()?;
+        let start = self.pos;
+        let (word, raw) = self.word();
+        let kind = match word.as_str() {
+            _ if raw => None,
+            "let" => {
+                let name = self.name()?;
+                self.expect("=")?;
+                Some(StmtKind::Let(name, self.expr(&span)?))
+            }
+            "set" => {
+                let ExprKind::Pick(arr, index) = self.expr(&span)?.kind else { return Err(self.error("expected `collection[index]`")) };
+                self.expect("=")?;
+                Some(StmtKind::Set(*arr, *index, self.expr(&span)?))
+            }
+            "push" => {
+                let arr = self.expr(&span)?;
+                self.expect(",")?;
+                Some(StmtKind::Push(arr, self.expr(&span)?))
+            }
+            "if" => {
+                let (mut branches, mut other) = (vec![], None);
+                loop {
+                    let condition = self.expr(&span)?;
+                    branches.push((condition, self.braced()?));
+                    if !self.keyword("else") { break }
+                    if !self.keyword("if") { other = Some(self.braced()?); break }
+                }
+                Some(StmtKind::If(branches, other))
+            }
+            "loop" => Some(StmtKind::Loop(self.braced()?)),
+            "block" => Some(StmtKind::Block(self.braced()?)),
+            "break" => Some(StmtKind::Break),
+            "continue" => Some(StmtKind::Continue),
+            "println" => Some(StmtKind::Println(self.expr(&span)?)),
+            "fn" => {
+                let name = self.name()?;
+                self.expect("(")?;
+                let mut args = vec![];
+                while !self.eat(")") {
+                    if !args.is_empty() { self.expect(",")?; }
+                    args.push(self.name()?);
+                }
+                Some(StmtKind::Fn(name, args, self.braced()?))
+            }
+            "mov" => Some(StmtKind::Mov(self.name()?)),
+            "stack" => Some(StmtKind::StackOp(self.name()?)),
+            "call" => {
+                let x = self.expr(&span)?;
+                let mut args = vec![];
+                if self.keyword("with") {
+                    args.push(self.expr(&span)?);
+                    while self.eat(",") { args.push(self.expr(&span)?); }
+                }
+                Some(StmtKind::CallQuote(x, args))
+            }
+            "array" => {
+                let word = self.name()?;
+                self.expect("(")?;
+                Some(StmtKind::ArrayOp(word, self.exprs(")", &span)?))
+            }
+            "rust" => { self.expect("(")?; Some(StmtKind::Rust(self.exprs(")", &span)?)) }
+            _ => None
+        };
+
+        let kind = match kind {
+            Some(x) => x,
+            None => {
+                self.pos = start;
+                let (name, _) = self.word();
+                if !name.is_empty() && self.eat("=") { StmtKind::Assign(name, self.expr(&span)?) }
+                else { self.pos = start; StmtKind::Expr(self.expr(&span)?) }
+            }
+        };
+        Ok(Stmt::new(kind, span))
+    }
+
+    // `span` is the span of the node around the expression
+    fn expr (&mut self, span: &Span) -> Parsed<Expr> {
+        self.skip();
+        let span = if self.at(0) == Some('@') {
+            self.pos += 1;
+            let loc = if self.at(0) == Some('"') { let x = self.string()?; self.expect(":")?; x.into() } else { span.loc.clone() };
+            let line = self.integer()?;
+            self.expect(":")?;
+            Span { loc, line, col: self.integer()? }
+        }
+        else { span.clone() };
+
+        let mut x = self.atom(&span)?;
+        while self.at(0) == Some('[') {
+            self.pos += 1;
+            let index = self.expr(&span)?;
+            self.expect("]")?;
+            x = Expr::new(ExprKind::Pick(Box::new(x), Box::new(index)), span.clone());
+        }
+        Ok(x)
+    }
+
+    fn atom (&mut self, span: &Span) -> Parsed<Expr> {
+        self.skip();
+        let Some(c) = self.at(0) else { return Err(self.error("expected an expression")) };
+        let kind = match c {
+            '"' => ExprKind::String(self.string()?),
+            '&' => { self.pos += 1; ExprKind::Ref(Box::new(self.expr(span)?)) }
+            '!' => { self.pos += 1; ExprKind::Not(Box::new(self.expr(span)?)) }
+            '(' => {
+                self.pos += 1;
+                let a = self.expr(span)?;
+                // expression in parentheses
+                if self.eat(")") { return Ok(a) }
+                self.skip();
+                let start = self.pos;
+                while self.at(0).is_some_and(|x| "+-*/%<>=!&|".contains(x)) { self.pos += 1; }
+                let Some(op) = BinOp::parse(&self.chars[start..self.pos].iter().collect::<String>()) else { self.pos = start; return Err(self.error("expected an operator")) };
+                let b = self.expr(span)?;
+                self.expect(")")?;
+                ExprKind::Binary(op, Box::new(a), Box::new(b))
+            }
+            '[' => { self.pos += 1; ExprKind::Array(self.items(']', span)?) }
+            '{' => { self.pos += 1; ExprKind::Block(self.items('}', span)?) }
+            c if c.is_ascii_digit() || c == '-' => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.at(0).is_some_and(|x| x.is_alphanumeric() || x == '.') { self.pos += 1; }
+                let x: String = self.chars[start..self.pos].iter().collect();
+                ExprKind::Number(x.parse().map_err(|_| { self.pos = start; self.error(format!("invalid number `{x}`")) })?)
+            }
+            _ => {
+                let start = self.pos;
+                let (word, raw) = self.word();
+                if word.is_empty() { return Err(self.error("expected an expression")) }
+                match word.as_str() {
+                    _ if raw => self.name_expr(word, span)?,
+                    "true" | "false" => ExprKind::Boolean(word == "true"),
+                    "undefined" => ExprKind::Undefined,
+                    "inf" | "NaN" => ExprKind::Number(word.parse().unwrap()),
+                    "do" => { self.expect("{")?; ExprKind::Quote(self.items('}', span)?) }
+                    "dict!" => {
+                        self.expect("(")?;
+                        self.expect("[")?;
+                        let k = self.items(']', span)?;
+                        self.expect(",")?;
+                        self.expect("[")?;
+                        let v = self.items(']', span)?;
+                        self.expect(")")?;
+                        ExprKind::Dict(k, v)
+                    }
+                    _ if word.ends_with('!') => {
+                        self.expect("(")?;
+                        let mut args = self.exprs(")", span)?;
+                        let operands = match word.as_str() { "type!" => 1, "curry!" | "compose!" => 2, _ => args.len() };
+                        if args.len() != operands { self.pos = start; return Err(self.error(format!("`{word}` expects {operands} operands"))) }
+                        match word.as_str() {
+                            "type!" => ExprKind::Type(Box::new(args.remove(0))),
+                            "curry!" => ExprKind::Curry(Box::new(args.remove(0)), Box::new(args.remove(0))),
+                            "compose!" => ExprKind::Compose(Box::new(args.remove(0)), Box::new(args.remove(0))),
+                            "rust!" => ExprKind::Rust(args),
+                            _ => ExprKind::Builtin(word.trim_end_matches('!').to_string(), args)
+                        }
+                    }
+                    _ => self.name_expr(word, span)?
+                }
+            }
+        };
+        Ok(Expr::new(kind, span.clone()))
+    }
+
+    // variable or call of the function
+    fn name_expr (&mut self, name: String, span: &Span) -> Parsed<ExprKind> {
+        if self.at(0) != Some('(') { return Ok(ExprKind::Get(name)) }
+        self.pos += 1;
+        Ok(ExprKind::Call(name, self.exprs(")", span)?))
+    }
+
+    // body of an expression: statements on their own lines, or expressions separated by commas
+    fn items (&mut self, close: char, span: &Span) -> Parsed<Body> {
+        if self.at_stmt() { return self.body(Some(close)) }
+        let exprs = self.exprs(&close.to_string(), span)?;
+        Ok(Body { stmts: exprs.into_iter().map(|x| Stmt::new(StmtKind::Expr(x), span.clone())).collect() })
+    }
+
+    // string literal with escapes of `{:?}`
+    fn string (&mut self) -> Parsed<String> {
+        self.expect("\"")?;
+        let mut x = String::new();
+        loop {
+            let Some(c) = self.at(0) else { return Err(self.error("unterminated string")) };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(x),
+                '\\' => {
+                    let Some(c) = self.at(0) else { return Err(self.error("unterminated string")) };
+                    self.pos += 1;
+                    x.push(match c {
+                        'n' => '\n', 'r' => '\r', 't' => '\t', '0' => '\0', '\\' | '"' | '\'' => c,
+                        'u' if self.at(0) == Some('{') => {
+                            let start = self.pos + 1;
+                            while self.at(0).is_some_and(|x| x != '}') { self.pos += 1; }
+                            self.pos += 1;
+                            let code: String = self.chars[start..self.pos - 1].iter().collect();
+                            u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).ok_or_else(|| self.error(format!("invalid character code `{code}`")))?
+                        }
+                        _ => { self.pos -= 1; return Err(self.error(format!("unknown escape `\\{c}`"))) }
+                    });
+                }
+                c => x.push(c)
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs::{read_dir, read_to_string, write}, path::Path};
+    use crate::{lexer::lex, transpiler::transpile};
+    use crate::bytecode::{binds::Binds, gen::get_statements, ir::Body};
+    use super::{parse, print};
+
+    fn generate (code: &str) -> Body {
+        get_statements(&lex(code.to_string(), vec![]), &mut Binds::new()).unwrap()
+    }
+
+    // IR of every example, with values folded at compile time, is pinned by `tests/golden/<example>.ir`.
+    // `ALT_UPDATE_GOLDEN=1 cargo test` writes them again
+    #[test]
+    fn examples_match_golden_files () {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut examples: Vec<_> = read_dir(root.join("examples")).unwrap().map(|x| x.unwrap().path()).collect();
+        examples.sort();
+        for path in examples {
+            let ir = generate(&read_to_string(&path).unwrap());
+            let text = print(&ir);
+            let golden = root.join("tests/golden").join(path.with_extension("ir").file_name().unwrap());
+            if env::var_os("ALT_UPDATE_GOLDEN").is_some() { write(&golden, &text).unwrap(); }
+
+            assert_eq!(text, read_to_string(&golden).unwrap(), "IR of `{}` differs from `{}`", path.display(), golden.display());
+            let parsed = parse(&text).unwrap();
+            assert_eq!(transpile(parsed.clone(), &mut HashMap::new()), transpile(ir.clone(), &mut HashMap::new()));
+            assert_eq!(parsed, ir);
+        }
+    }
+
+    #[test]
+    fn ir_round_trips () {
+        let ir = generate(r#"
+            fn fact n { n 1 <= if { 1 } else { n 1 - fact n * } }
+            5 fact println
+            0 -> i
+            loop { i 1 + -> i i 3 > if { break } }
+            [ 1 2 3 ] -> a
+            a 0 10 set a 4 push
+            a do { 2 * } map println
+            "tab\t\"quoted\"\n" println
+            [ "a" "b" ] [ do { "hi" } do { "there" } ] dict -> d  d "b" pick call println
+            do { 3 + } do { 2 * } compose 4 swap call println
+            a sum println a type println
+        "#);
+        assert_eq!(parse(&print(&ir)).unwrap(), ir);
+    }
+
+    #[test]
+    fn text_round_trips () {
+        let text = r#"1:1     let r#loop = [inf, -inf, -0, 0.5, "\u{1b}[0m", undefined]
+# lib.alt
+2:1     fn r#sum!(x) {
+2:10        x
+        }
+2:20    if !(&r#loop)[0] {
+3:1         println sum!(@1:1 r#loop, rust!("1", x))
+        } else if @2:30 (r#sum!(1) != @"other.alt":7:7 2) {
+3:5         block {
+3:9             stack dup
+            }
+        }
+#
+4:1     call curry!(1, do {
+4:5         mov x
+4:7         {x, [x, true]}
+        }) with 1, 2
+4:20    rust("let y = ", x, &"s")
+"#;
+        assert_eq!(print(&parse(text).unwrap()), text);
+    }
+
+    #[test]
+    fn ir_is_transpiled () {
+        let ir = parse("1:1     let x = 5\n2:1     println (x + 1)\n").unwrap();
+        let rust = transpile(ir, &mut HashMap::new());
+        assert!(rust.contains("let mut _v_x = nvar!(Value::Number(5.0));"), "{rust}");
+        assert!(rust.contains(r#"println!("{}", (_v_x.clone() + Value::Number(1.0)));"#), "{rust}");
+    }
+
+    #[test]
+    fn errors_point_to_the_text () {
+        let e = parse("1:1     let x = 5\n2:1     println (x +)\n").unwrap_err();
+        assert_eq!((e.message.as_str(), e.span.line, e.span.col), ("expected an expression", 2, 21));
+    }
+}
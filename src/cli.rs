@@ -0,0 +1,135 @@
+use std::path::Path;
+
+pub const USAGE: &str = "\
+alt - compiler of alt, concatenative programming language
+
+Usage: alt <command> <file.alt> [options]
+       alt repl [file.alt]
+
+Commands:
+    build         compile program into executable
+    run           run program with interpreter, or compile and run it with `--compiled`
+                  (arguments after `--` are passed to the program)
+    check         report errors without producing anything
+    emit-rust     print generated Rust code (or write it to `-o` file)
+    emit-bytecode write compiled module to `.altc` file (or `-o` file), which can be given instead of alt code
+    dump-tokens   print tokens produced by the lexer
+    dump-ir       print intermediate representation of the program
+                  (saved to `.ir` file, it can be compiled instead of alt code)
+    disasm        print instructions of the program for virtual machine
+    repl          start interactive prompt, optionally running the file first
+    help          print this message
+
+Options:
+    -o, --output <path>     where to put the result (default: name of input file without extension)
+        --opt-level <n>     rustc optimization level: 0, 1, 2, 3, s or z (default: 3)
+        --compiled          `run`: compile program with rustc instead of interpreting it
+        --vm                `run`: run program with virtual machine instead of interpreting it
+        --build-dir <path>  where to put generated code and runtime and keep them (default: temporary directory)
+        --unroll-limit <n>  biggest count of `times` unrolled at compile time, bigger ones are loops (default: 256)
+    -q, --quiet             print only errors
+    -v, --verbose           print what compiler does
+        --lex-warnings      report unrecognized characters as warnings instead of errors
+        --regex-lexer       lex with regex rules (slow, useful to check the lexer)
+    -h, --help              print this message
+    -V, --version           print version";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Build, Run, Check, EmitRust, EmitBytecode, DumpTokens, DumpIr, Disasm, Repl
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet, Normal, Verbose
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub input: String,
+    pub output: Option<String>,
+    pub opt_level: String,
+    pub build_dir: Option<String>,
+    pub unroll_limit: Option<usize>,
+    pub compiled: bool,
+    pub vm: bool,
+    pub verbosity: Verbosity,
+    pub lex_warnings: bool,
+    pub regex_lexer: bool,
+    pub program_args: Vec<String>
+}
+
+// what to do after parsing arguments
+pub enum Action {
+    Compile(Options), Help, Version
+}
+
+pub fn parse_args (args: &[String]) -> Result<Action, String> {
+    let mut args = args.iter();
+    let command = match args.next().map(|x| x.as_str()) {
+        Some("build") => Command::Build,
+        Some("run") => Command::Run,
+        Some("check") => Command::Check,
+        Some("emit-rust") => Command::EmitRust,
+        Some("emit-bytecode") => Command::EmitBytecode,
+        Some("dump-tokens") => Command::DumpTokens,
+        Some("dump-ir") => Command::DumpIr,
+        Some("disasm") => Command::Disasm,
+        Some("repl") => Command::Repl,
+        Some("help" | "-h" | "--help") | None => return Ok(Action::Help),
+        Some("-V" | "--version") => return Ok(Action::Version),
+        Some(x) => return Err(format!("unknown command `{x}`"))
+    };
+
+    let mut options = Options {
+        command, input: String::new(), output: None, opt_level: "3".to_string(), build_dir: None, unroll_limit: None, compiled: false, vm: false,
+        verbosity: Verbosity::Normal, lex_warnings: false, regex_lexer: false, program_args: vec![]
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => options.output = Some(args.next().ok_or(format!("`{arg}` expects a path"))?.clone()),
+            "--opt-level" => {
+                let level = args.next().ok_or("`--opt-level` expects a level")?;
+                if !["0", "1", "2", "3", "s", "z"].contains(&level.as_str()) { return Err(format!("invalid optimization level `{level}`, expected 0, 1, 2, 3, s or z")) }
+                options.opt_level = level.clone();
+            }
+            "--unroll-limit" => {
+                let limit = args.next().ok_or("`--unroll-limit` expects a number")?;
+                options.unroll_limit = Some(limit.parse().map_err(|_| format!("invalid unroll limit `{limit}`, expected a non-negative integer"))?);
+            }
+            "--compiled" => options.compiled = true,
+            "--vm" => options.vm = true,
+            "--build-dir" => options.build_dir = Some(args.next().ok_or("`--build-dir` expects a path")?.clone()),
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--lex-warnings" => options.lex_warnings = true,
+            "--regex-lexer" => options.regex_lexer = true,
+            "-h" | "--help" => return Ok(Action::Help),
+            "--" if command == Command::Run => { options.program_args = args.cloned().collect(); break }
+            x if x.starts_with('-') && x.len() > 1 => return Err(format!("unknown option `{x}`")),
+            x => {
+                if !options.input.is_empty() { return Err(format!("unexpected argument `{x}`, input file is already `{}`", options.input)) }
+                options.input = x.to_string();
+            }
+        }
+    }
+
+    if options.input.is_empty() && command != Command::Repl { return Err("no input file".to_string()) }
+    Ok(Action::Compile(options))
+}
+
+impl Options {
+    // path of executable for `build`: `-o` or name of input file without extension
+    pub fn executable (&self) -> String {
+        if let Some(x) = &self.output { return x.clone() }
+        let stem = Path::new(&self.input).file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or("out".to_string());
+        if cfg!(windows) { format!("{stem}.exe") } else { stem }
+    }
+
+    // path of module for `emit-bytecode`: `-o` or input file with `.altc` extension
+    pub fn module (&self) -> String {
+        if let Some(x) = &self.output { return x.clone() }
+        Path::new(&self.input).with_extension("altc").to_string_lossy().to_string()
+    }
+}
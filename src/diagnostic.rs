@@ -0,0 +1,99 @@
+use std::{fmt, fs::read_to_string, rc::Rc};
+
+use crate::lexer::Token;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error, Warning
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub line: usize, pub col: usize, pub len: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    // file of `use`d code, empty for the compiled one
+    pub span: Span, pub loc: Rc<str>,
+    pub notes: Vec<String>,
+    pub help: Option<String>
+}
+
+impl Diagnostic {
+
+    pub fn new (severity: Severity, token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span: Span { line: token.line, col: token.col, len: token.value.chars().count().max(1) },
+            loc: token.loc.as_str().into(),
+            notes: vec![],
+            help: None
+        }
+    }
+
+    pub fn error (token: &Token, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, token, message)
+    }
+
+    // points diagnostic to a part of the token
+    pub fn at (mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn note (mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn help (mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error (&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // renders diagnostic in rustc-like way: header, location, source line with caret underline, notes and help
+    pub fn render (&self, source: &str, path: &str) -> String {
+        let path = if self.loc.is_empty() { path } else { &self.loc };
+        let line_no = self.span.line.to_string();
+        let pad = " ".repeat(line_no.len());
+
+        let mut out = format!("{}: {}\n{pad}--> {path}:{}:{}\n", self.severity, self.message, self.span.line, self.span.col);
+        if let Some(line) = source.lines().nth(self.span.line.saturating_sub(1)) {
+            let line = line.trim_end();
+            // keep tabs so caret stays under the token
+            let offset: String = line.chars().take(self.span.col.saturating_sub(1)).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            let len = self.span.len.min(line.chars().count().saturating_sub(offset.chars().count())).max(1);
+            out += &format!("{pad} |\n{line_no} | {line}\n{pad} | {offset}{}\n", "^".repeat(len));
+        }
+        for note in &self.notes { out += &format!("{pad} = note: {note}\n"); }
+        if let Some(help) = &self.help { out += &format!("{pad} = help: {help}\n"); }
+
+        out
+    }
+
+}
+
+impl fmt::Display for Severity {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning")
+        }
+    }
+}
+
+// renders all diagnostics, reading sources of `use`d files when diagnostic points to them
+pub fn render_all (diagnostics: &[Diagnostic], source: &str, path: &str) -> String {
+    diagnostics.iter().map(|d| {
+        if d.loc.is_empty() || *d.loc == *path { return d.render(source, path) }
+        d.render(&read_to_string(&*d.loc).unwrap_or_default(), path)
+    }).collect::<Vec<String>>().join("\n")
+}
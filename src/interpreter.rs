@@ -0,0 +1,352 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::{Arc, Mutex}};
+
+use crate::alt::{seq, value::Value as Data, r#ref::Ref, quote::{Quote, curry, compose}, collections::dict, ops::set};
+use crate::bytecode::{ir::{BinOp, Body, Expr, ExprKind, Span, Stmt, StmtKind}, value::{stack_effect, values}};
+use crate::diagnostic::{self, Diagnostic, Severity};
+
+// executes instructions directly, with the same semantics as code generated by transpiler
+
+// what happens after statement
+enum Flow {
+    Next, Break, Continue, Return(Data)
+}
+
+// what to do with values of statements: push them to the stack (top-level, arrays) or return first of them (functions, blocks used as values)
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Push, Return
+}
+
+// arguments and body
+type Function = Rc<(Vec<String>, Body)>;
+
+pub struct Interpreter {
+    pub stack: Vec<Data>,
+    scopes: Vec<HashMap<String, Ref>>,
+    functions: HashMap<String, Function>,
+    // span of the statement which is executed
+    loc: Span,
+    // error inside of quotation, which can't return it through the call
+    error: Rc<RefCell<Option<Diagnostic>>>
+}
+
+// stack, variables and functions between runs. variables keep their cells, which can be shared with quotations on the stack
+pub struct Snapshot {
+    stack: Vec<Data>,
+    variables: Vec<(String, Ref, Data)>,
+    functions: HashMap<String, Function>
+}
+
+impl Interpreter {
+
+    pub fn new () -> Self {
+        Self { stack: vec![], scopes: vec![HashMap::new()], functions: HashMap::new(), loc: Span::default(), error: Rc::new(RefCell::new(None)) }
+    }
+
+    // runs top-level instructions. variables, functions and stack are kept between runs
+    pub fn run (&mut self, program: &Body) -> Result<(), Diagnostic> {
+        self.body(program, Mode::Push)?;
+        Ok(())
+    }
+
+    // binds value to variable of top-level scope
+    pub fn snapshot (&self) -> Snapshot {
+        let variables = self.scopes[0].iter().map(|(k, v)| (k.clone(), v.clone_ref(), v.lock().clone())).collect();
+        Snapshot { stack: self.stack.clone(), variables, functions: self.functions.clone() }
+    }
+
+    // goes back to the snapshot after a failed run
+    pub fn restore (&mut self, snapshot: Snapshot) {
+        self.stack = snapshot.stack;
+        self.scopes = vec![snapshot.variables.into_iter().map(|(k, v, x)| { *v.lock() = x; (k, v) }).collect()];
+        self.functions = snapshot.functions;
+        self.error.borrow_mut().take();
+    }
+
+    fn body (&mut self, body: &Body, mode: Mode) -> Result<Flow, Diagnostic> {
+        // functions can be called before their definition, like items in generated code
+        for i in &body.stmts {
+            if let StmtKind::Fn(name, args, body) = &i.kind { self.functions.insert(name.clone(), Rc::new((args.clone(), body.clone()))); }
+        }
+
+        for i in &body.stmts {
+            self.loc = i.span.clone();
+            let flow = self.statement(i, mode)?;
+            if let Flow::Next = flow { continue }
+            return Ok(flow)
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn scoped (&mut self, body: &Body, mode: Mode) -> Result<Flow, Diagnostic> {
+        self.scopes.push(HashMap::new());
+        let flow = self.body(body, mode);
+        self.scopes.pop();
+        flow
+    }
+
+    fn statement (&mut self, stmt: &Stmt, mode: Mode) -> Result<Flow, Diagnostic> {
+        match &stmt.kind {
+            StmtKind::Expr(x) => {
+                let x = self.eval(x)?;
+                if mode == Mode::Return { return Ok(Flow::Return(x)) }
+                if x != Data::Empty { self.stack.push(x); }
+            }
+
+            StmtKind::Block(body) => return self.scoped(body, mode),
+
+            StmtKind::Let(name, value) => {
+                if name == "_" { self.eval(value)?; return Ok(Flow::Next) }
+                let x = self.as_ref(value)?;
+                self.declare(name, x);
+            }
+            StmtKind::Assign(name, value) => {
+                let x = self.eval(value)?;
+                match self.lookup(name) {
+                    Some(var) => *var.lock() = x,
+                    None => self.declare(name, cell(x))
+                }
+            }
+            StmtKind::Set(arr, index, value) => {
+                let index = self.eval(index)?;
+                let value = self.eval(value)?;
+                let arr = match &arr.kind {
+                    ExprKind::Get(name) => self.var(name)?.clone_ref(),
+                    _ => self.as_ref(arr)?
+                };
+                set(arr, index, value);
+            }
+            StmtKind::Push(arr, value) => {
+                let value = self.eval(value)?;
+                let mut arr = match &arr.kind {
+                    ExprKind::Get(name) => Data::Ref(self.var(name)?.clone_ref()),
+                    _ => self.eval(arr)?
+                };
+                arr.push(value);
+            }
+
+            StmtKind::If(branches, other) => {
+                for (condition, body) in branches {
+                    if self.eval(condition)?.cast_bool() { return self.scoped(body, mode) }
+                }
+                if let Some(body) = other { return self.scoped(body, mode) }
+            }
+            StmtKind::Loop(body) => {
+                loop {
+                    match self.scoped(body, mode)? {
+                        Flow::Break => break,
+                        Flow::Return(x) => return Ok(Flow::Return(x)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+
+            StmtKind::Println(x) => { let x = self.eval(x)?; println!("{x}"); }
+            StmtKind::Fn(..) => {}
+
+            StmtKind::Mov(name) => {
+                let Some(x) = self.stack.pop() else { return Err(self.error("stack underflow: not enough values on the stack")) };
+                if name != "_" { self.declare(name, cell(x)); }
+            }
+            StmtKind::StackOp(word) => {
+                let (n, result) = stack_effect(word).unwrap();
+                if self.stack.len() < n { return Err(self.error(format!("stack underflow: `{word}` expects {}", values(n)))) }
+                let values = self.stack.split_off(self.stack.len() - n);
+                self.stack.extend(result.iter().map(|i| values[*i].clone()));
+            }
+            StmtKind::CallQuote(x, args) => {
+                for i in args { let x = self.eval(i)?; if x != Data::Empty { self.stack.push(x); } }
+                let x = self.eval(x)?;
+                self.callable(x)?.call(&mut self.stack);
+                if let Some(e) = self.error.borrow_mut().take() { return Err(e) }
+            }
+            StmtKind::ArrayOp(word, args) => {
+                let mut args = args.iter().map(|x| self.eval(x)).collect::<Result<Vec<Data>, Diagnostic>>()?;
+                let q = Data::Quote(self.callable(args.pop().unwrap())?);
+                let (arr, stack) = (args.remove(0), &mut self.stack);
+                match word.as_str() {
+                    "map" => seq::map(arr, q, stack),
+                    "filter" => seq::filter(arr, q, stack),
+                    "reduce" => seq::reduce(arr, args.remove(0), q, stack),
+                    "each" => seq::each(arr, q, stack),
+                    "each-index" => seq::each_index(arr, q, stack),
+                    "any" => seq::any(arr, q, stack),
+                    "all" => seq::all(arr, q, stack),
+                    "find" => seq::find(arr, q, stack),
+                    _ => seq::count(arr, q, stack)
+                }
+                if let Some(e) = self.error.borrow_mut().take() { return Err(e) }
+            }
+            StmtKind::Rust(_) => return Err(self.error("`:rust!` cannot be interpreted").help("compile the program: `alt run --compiled`"))
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn eval (&mut self, expr: &Expr) -> Result<Data, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Number(x) => Ok(Data::Number(*x)),
+            ExprKind::String(x) => Ok(Data::String(x.clone())),
+            ExprKind::Boolean(x) => Ok(Data::Boolean(*x)),
+            ExprKind::Undefined => Ok(Data::Undefined),
+            ExprKind::Get(name) => Ok(self.var(name)?.lock().clone()),
+
+            ExprKind::Binary(op, a, b) if !op.is_logical() => {
+                let (x, y) = (self.eval(a)?, self.eval(b)?);
+                Ok(match op {
+                    BinOp::Add => x + y, BinOp::Sub => x - y, BinOp::Mul => x * y, BinOp::Div => x / y, BinOp::Rem => x % y,
+                    BinOp::Shl => x << y, BinOp::Shr => x >> y, _ => x.pow(y)
+                })
+            }
+            ExprKind::Binary(op, a, b) => {
+                let x = self.eval(a)?;
+                Ok(Data::Boolean(match op {
+                    BinOp::And => x.cast_bool() && self.eval(b)?.cast_bool(),
+                    BinOp::Or => x.cast_bool() || self.eval(b)?.cast_bool(),
+                    BinOp::Eq => x == self.eval(b)?,
+                    BinOp::Ne => x != self.eval(b)?,
+                    BinOp::Lt => x.cast_float() < self.eval(b)?.cast_float(),
+                    BinOp::Gt => x.cast_float() > self.eval(b)?.cast_float(),
+                    BinOp::Le => x.cast_float() <= self.eval(b)?.cast_float(),
+                    _ => x.cast_float() >= self.eval(b)?.cast_float()
+                }))
+            }
+            ExprKind::Not(x) => Ok(Data::Boolean(!self.eval(x)?.cast_bool())),
+
+            ExprKind::Ref(_) => Ok(Data::Ref(self.as_ref(expr)?)),
+            ExprKind::Call(name, args) => {
+                let Some(function) = self.functions.get(name).cloned() else { return Err(self.error(format!("unknown function `{name}`"))) };
+                let mut scope = HashMap::new();
+                for (name, arg) in function.0.iter().zip(args) { scope.insert(name.clone(), self.as_ref(arg)?); }
+
+                let scopes = std::mem::replace(&mut self.scopes, vec![scope]);
+                let stack = std::mem::take(&mut self.stack);
+                let loc = self.loc.clone();
+                let flow = self.body(&function.1, Mode::Return);
+                self.scopes = scopes;
+                let mut stack = std::mem::replace(&mut self.stack, stack);
+                // function which doesn't return a value gives the top of its stack
+                let x = match flow? { Flow::Return(x) => x, _ => stack.pop().unwrap_or(Data::Empty) };
+                self.loc = loc;
+                Ok(x)
+            }
+
+            ExprKind::Array(body) => Ok(Data::Arr(self.array(body)?)),
+            ExprKind::Block(body) => {
+                match self.scoped(body, Mode::Return)? {
+                    Flow::Return(x) => Ok(x),
+                    Flow::Next => Ok(Data::Empty),
+                    Flow::Break | Flow::Continue => Err(self.error("`break` and `continue` cannot be used in blocks used as values"))
+                }
+            }
+            ExprKind::Dict(k, v) => {
+                let (k, v) = (self.array(k)?, self.array(v)?);
+                if k.len() != v.len() { return Err(self.error(format!("failed to create dictionary: shapes [{}] and [{}] don't match", k.len(), v.len()))) }
+                Ok(Data::Dict(dict(k, v)))
+            }
+            ExprKind::Pick(arr, index) => {
+                let arr = self.eval(arr)?;
+                let index = self.eval(index)?;
+                Ok(arr[index].clone())
+            }
+            ExprKind::Type(x) => Ok(Data::String(self.eval(x)?.cast_type())),
+            ExprKind::Rust(_) => Err(self.error("`:rust!:` cannot be interpreted").help("compile the program: `alt run --compiled`")),
+            ExprKind::Builtin(word, args) => {
+                let mut args = args.iter().map(|x| self.eval(x)).collect::<Result<Vec<Data>, Diagnostic>>()?.into_iter();
+                let mut a = args.next().unwrap();
+                Ok(match word.as_str() {
+                    "sum" => a.sum(),
+                    "concat" => a.concat(args.next().unwrap()),
+                    "flat" => a.flat(),
+                    "inc" => { a.inc(); a }
+                    "dec" => { a.dec(); a }
+                    "int" => Data::Number(a.cast_int() as f64),
+                    _ => Data::Arr(a.cast_vec())
+                })
+            }
+            ExprKind::Curry(x, q) => {
+                let (x, q) = (self.eval(x)?, self.eval(q)?);
+                Ok(curry(x, self.quoted(q)))
+            }
+            ExprKind::Compose(p, q) => {
+                let (p, q) = (self.eval(p)?, self.eval(q)?);
+                Ok(compose(self.quoted(p), self.quoted(q)))
+            }
+            ExprKind::Quote(body) => {
+                // quotation runs in its own interpreter, sharing variables and functions visible where it's created
+                let scope: HashMap<String, Ref> = self.scopes.iter().flatten().map(|(k, v)| (k.clone(), v.clone_ref())).collect();
+                let (functions, loc, error, body) = (self.functions.clone(), self.loc.clone(), self.error.clone(), body.clone());
+                Ok(Data::Quote(Quote::new(move |stack| {
+                    let mut interpreter = Interpreter { stack: std::mem::take(stack), scopes: vec![scope.clone()], functions: functions.clone(), loc: loc.clone(), error: error.clone() };
+                    let flow = interpreter.body(&body, Mode::Push);
+                    *stack = interpreter.stack;
+                    if let Err(e) = flow { error.borrow_mut().get_or_insert(e); }
+                })))
+            }
+        }
+    }
+
+    fn array (&mut self, body: &Body) -> Result<Vec<Data>, Diagnostic> {
+        if body.is_static() {
+            return body.stmts.iter().map(|x| match &x.kind { StmtKind::Expr(x) => self.eval(x), _ => unreachable!() }).collect()
+        }
+
+        let stack = std::mem::take(&mut self.stack);
+        let flow = self.scoped(body, Mode::Push);
+        let result = std::mem::replace(&mut self.stack, stack);
+        flow?;
+        Ok(result)
+    }
+
+    // reference to variable itself for `x &`, new reference for any other value
+    fn as_ref (&mut self, expr: &Expr) -> Result<Ref, Diagnostic> {
+        if let ExprKind::Ref(x) = &expr.kind {
+            if let ExprKind::Get(name) = &x.kind { return Ok(self.var(name)?.clone_ref()) }
+        }
+        Ok(cell(self.eval(expr)?))
+    }
+
+    // quotation to call, generated code panics on other values
+    fn callable (&self, mut x: Data) -> Result<Quote, Diagnostic> {
+        while let Data::Ref(r) = x { x = r.clone(); }
+        match x {
+            Data::Quote(x) => Ok(x),
+            x => Err(self.error(format!("cannot call {}", x.cast_type())).help("create quotation with `do {...}`"))
+        }
+    }
+
+    // operand of `curry` and `compose`. other values than quotations give the error of `call` when the result is called
+    fn quoted (&self, x: Data) -> Data {
+        let Err(e) = self.callable(x.clone()) else { return x };
+        let error = self.error.clone();
+        Data::Quote(Quote::new(move |_| { error.borrow_mut().get_or_insert(e.clone()); }))
+    }
+
+    fn lookup (&self, name: &str) -> Option<Ref> {
+        self.scopes.iter().rev().find_map(|x| x.get(name)).map(|x| x.clone_ref())
+    }
+
+    fn var (&self, name: &str) -> Result<Ref, Diagnostic> {
+        self.lookup(name).ok_or_else(|| self.error(format!("variable `{name}` is not defined here")))
+    }
+
+    fn declare (&mut self, name: &str, value: Ref) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), value);
+    }
+
+    // error at the statement which is executed now
+    fn error (&self, message: impl Into<String>) -> Diagnostic {
+        let Span { loc, line, col } = &self.loc;
+        Diagnostic { severity: Severity::Error, message: message.into(), span: diagnostic::Span { line: *line, col: *col, len: 1 }, loc: loc.clone(), notes: vec![], help: None }
+    }
+
+}
+
+// variable cell, like `nvar!` in generated code. values aren't sent to other threads
+#[allow(clippy::arc_with_non_send_sync)]
+pub fn cell (x: Data) -> Ref {
+    Ref::new(Arc::new(Mutex::new(x)))
+}
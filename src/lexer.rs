@@ -1,4 +1,5 @@
 use fancy_regex::Regex;
+use crate::diagnostic::{Diagnostic, Severity};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TokenKind {
@@ -6,14 +7,16 @@ pub enum TokenKind {
     Int, Real, Operator,
     Keyword, Comment, Assign,
     Bracket, CurlyBracket, Logical,
-    SpecialSymbol
+    SpecialSymbol,
+    Unknown /* characters that no rule matched */
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub typ: TokenKind,
     pub value: String,
-    pub line: usize, pub col: usize, pub loc: String
+    pub line: usize, pub col: usize, pub loc: String,
+    pub doc: Option<String> /* `///` comments before `fn` and `macro` */
 }
 
 #[derive(Clone)]
@@ -22,36 +25,289 @@ pub struct Rule {
     pub typ: TokenKind
 }
 
+// `ruleset` is an extension layer: its rules are tried before built-in ones, so they can add or override tokens.
+// Built-in tokens are scanned by hand in one pass, `get_lexer_rules` describes the same grammar with regexes
 pub fn lex (code: String, ruleset: Vec<Rule>) -> Vec<Token> {
+    scan(code, &ruleset, true)
+}
+
+// lexes only with `rules`, without built-in tokens. with `get_lexer_rules` it's the regex lexer the hand-written one is checked against
+pub fn lex_rules (code: String, rules: &[Rule]) -> Vec<Token> {
+    scan(code, rules, false)
+}
+
+fn scan (code: String, ruleset: &[Rule], builtin: bool) -> Vec<Token> {
+    // rules match only at the beginning of the rest of the code
+    let ruleset: Vec<Rule> = ruleset.iter().map(|x| Rule { regex: Regex::new(&format!("^(?:{})", x.regex.as_str())).unwrap(), typ: x.typ }).collect();
     let mut tokens: Vec<Token> = vec![];
     let code_chars = code.chars().collect::<Vec<char>>();
 
     let mut col = 0;
-    let mut loc_col = 0;
+    let mut byte = 0;
+    let mut loc_col = 1;
     let mut line = 1;
     while col < code_chars.len() {
-        if code_chars[col] == '\n' { line += 1; loc_col = 0; }
-        match get_token(&code_chars[col..], &ruleset, line, loc_col) {
-            Some(x) => {tokens.push(x.0); col += x.1; loc_col += x.1;}
-            None => {col += 1; loc_col += 1;}
+        match match_rule(&code[byte..], &ruleset).or_else(|| if builtin { scan_token(&code_chars[col..]) } else { None }) {
+            Some((typ, len)) => {
+                let value: String = code_chars[col..col+len].iter().collect();
+                let token = Token { typ, value, line, col: loc_col, loc: String::new(), doc: None };
+                // tokens like comments and strings can take several lines
+                for c in &code_chars[col..col+len] {
+                    if *c == '\n' { line += 1; loc_col = 1; } else { loc_col += 1; }
+                }
+                byte += token.value.len();
+                tokens.push(token); col += len;
+            }
+            None => {
+                let c = code_chars[col];
+                let len = if code_chars[col..].starts_with(&[';', ';']) || code_chars[col..].starts_with(&['/', '*']) { 2 } else { 1 };
+                if !c.is_whitespace() {
+                    match tokens.last_mut() {
+                        // glue unknown characters together, so `@@@` is reported once
+                        Some(x) if x.typ == TokenKind::Unknown && x.line == line && x.col + x.value.chars().count() == loc_col && len == 1 && !is_unterminated(x) => x.value.push(c),
+                        _ => tokens.push(Token { typ: TokenKind::Unknown, value: code_chars[col..col+len].iter().collect(), line, col: loc_col, loc: String::new(), doc: None })
+                    }
+                }
+                if c == '\n' { line += 1; loc_col = 1; } else { loc_col += len; }
+                byte += code_chars[col..col+len].iter().map(|x| x.len_utf8()).sum::<usize>();
+                col += len;
+            }
         }
     }
 
+    attach_docs(&mut tokens);
     tokens
 }
 
-fn get_token (code: &[char], ruleset: &Vec<Rule>, line: usize, col: usize) -> Option<(Token, usize)> {
+// attaches `///` comments to the `fn` or `macro` that follows them
+fn attach_docs (tokens: &mut [Token]) {
+    let mut doc: Vec<String> = vec![];
+    for token in tokens.iter_mut() {
+        if token.typ == TokenKind::Comment {
+            if let Some(x) = token.value.strip_prefix("///").filter(|x| !x.starts_with('/')) {
+                doc.push(x.strip_prefix(' ').unwrap_or(x).to_string());
+            }
+            continue
+        }
+        if !doc.is_empty() && token.typ == TokenKind::Keyword && matches!(token.value.as_str(), "fn" | "macro" | "macro:b:") {
+            token.doc = Some(doc.join("\n"));
+        }
+        doc.clear();
+    }
+}
+
+fn match_rule (code: &str, ruleset: &[Rule]) -> Option<(TokenKind, usize)> {
     for i in ruleset.iter() {
-        if let Some(x) = i.regex.find(code.iter().collect::<String>().as_str()).unwrap() {
-            let value = x.as_str().to_string();
-            let vlen = value.chars().count();
-            return Some((Token {
-                typ: i.typ,
-                value,
-                line, col, loc: String::new()
-            }, vlen))
+        if let Ok(Some(x)) = i.regex.find(code) {
+            if !x.as_str().is_empty() { return Some((i.typ, x.as_str().chars().count())) }
+        }
+    }
+
+    None
+}
+
+fn is_keyword_char (c: char) -> bool {
+    matches!(c, 'A'..='Z' | 'a'..='z' | 'А'..='Я' | 'а'..='я' | '_' | ':' | '!')
+}
+
+// block comment `/* ... */`, inner `/* */` pairs are nested
+fn scan_block_comment (code: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i + 1 < code.len() {
+        if code[i] == '/' && code[i+1] == '*' { depth += 1; i += 2; continue }
+        if code[i] == '*' && code[i+1] == '/' {
+            depth -= 1; i += 2;
+            if depth == 0 { return Some(i) }
+            continue
         }
+        i += 1;
     }
 
     None
-}
\ No newline at end of file
+}
+
+// finds `end` starting from `from` and returns length of token including `end`
+fn scan_until (code: &[char], from: usize, end: &[char]) -> Option<usize> {
+    (from..code.len()).find(|i| code[*i..].starts_with(end)).map(|i| i + end.len())
+}
+
+// numbers: `12`, `1_000`, `0xFF`, `0b1010`, `0o17`, `1.5`, `.5`, `1e-9`, optionally with `-` in front
+fn scan_number (code: &[char]) -> Option<(TokenKind, usize)> {
+    let at = |i: usize| code.get(i).copied().unwrap_or('\0');
+    // digit followed by digits and `_`
+    let digits = |from: usize| if at(from).is_ascii_digit() { code.iter().skip(from).take_while(|x| x.is_ascii_digit() || **x == '_').count() } else { 0 };
+
+    let sign = (code[0] == '-') as usize;
+    if at(sign) == '0' {
+        let radix = match at(sign + 1) { 'x' => 16, 'b' => 2, 'o' => 8, _ => 0 };
+        let len = code.iter().skip(sign + 2).take_while(|x| x.is_digit(radix.max(2)) || **x == '_').count();
+        if radix > 0 && len > 0 { return Some((TokenKind::Int, sign + 2 + len)) }
+    }
+
+    let mut len = sign + digits(sign);
+    let mut typ = TokenKind::Int;
+    if at(len) == '.' && at(len + 1).is_ascii_digit() { len += 1 + digits(len + 1); typ = TokenKind::Real; }
+    if len == sign { return None }
+
+    let exp_sign = matches!(at(len + 1), '+' | '-') as usize;
+    if matches!(at(len), 'e' | 'E') && at(len + 1 + exp_sign).is_ascii_digit() { len += 1 + exp_sign + digits(len + 1 + exp_sign); typ = TokenKind::Real; }
+
+    Some((typ, len))
+}
+
+// recognizes one token at the beginning of `code`. Order of checks follows order of rules in `get_lexer_rules`
+fn scan_token (code: &[char]) -> Option<(TokenKind, usize)> {
+    let at = |i: usize| code.get(i).copied().unwrap_or('\0');
+    let digits = |from: usize| code.iter().skip(from).take_while(|x| x.is_ascii_digit()).count();
+
+    // words which would be lexed as several tokens
+    for word in ["2dup", "2drop", "-rot", "bi@", "each-index"] {
+        let len = word.chars().count();
+        if code.iter().take(len).copied().eq(word.chars()) && !is_keyword_char(at(len)) && !at(len).is_ascii_digit() { return Some((TokenKind::Keyword, len)) }
+    }
+
+    if let Some(x) = scan_number(code) { return Some(x) }
+
+    if code.starts_with(&['r', '#', '"']) {
+        if let Some(len) = scan_until(code, 3, &['"', '#']) { return Some((TokenKind::String, len)) }
+    }
+
+    let token = match code[0] {
+        '"' | '\'' => {
+            let mut i = 1;
+            while i < code.len() {
+                if code[i] == code[0] { return Some((TokenKind::String, i + 1)) }
+                i += if code[i] == '\\' { 2 } else { 1 };
+            }
+            return None
+        }
+        ';' if at(1) == ';' => (TokenKind::Comment, scan_until(code, 2, &[';', ';'])?),
+        '/' if at(1) == '/' => (TokenKind::Comment, code.iter().take_while(|x| **x != '\n').count()),
+        '/' if at(1) == '*' => (TokenKind::Comment, scan_block_comment(code)?),
+        '-' if at(1) == '>' => (TokenKind::Assign, 2),
+        '=' if at(1) == ':' => (TokenKind::Assign, 2),
+        '*' if at(1) == '*' => (TokenKind::Operator, 2),
+        '<' | '>' if at(1) == code[0] => (TokenKind::Operator, 2),
+        '-' | '+' | '/' | '*' | '%' => (TokenKind::Operator, 1),
+        '{' | '}' => (TokenKind::CurlyBracket, 1),
+        '[' | ']' => (TokenKind::Bracket, 1),
+        '#' if at(1) == '!' => (TokenKind::SpecialSymbol, 2),
+        '!' if at(1) == '#' => (TokenKind::SpecialSymbol, 2),
+        '<' | '>' | '!' => (TokenKind::Logical, if at(1) == '=' { 2 } else { 1 }),
+        '=' => (TokenKind::Logical, 1),
+        '&' if at(1) == '&' => (TokenKind::Logical, 2),
+        '|' if at(1) == '|' => (TokenKind::Logical, 2),
+        '&' | '(' | ')' => (TokenKind::SpecialSymbol, 1),
+        c if is_keyword_char(c) => {
+            // `and`, `or` and `not` are operators even at the beginning of a word, like `or` of `order`
+            for word in ["and", "or", "not"] {
+                if code.iter().take(word.len()).copied().eq(word.chars()) { return Some((TokenKind::Logical, word.len())) }
+            }
+            let letters = code.iter().take_while(|x| is_keyword_char(**x)).count();
+            (TokenKind::Keyword, letters + digits(letters))
+        }
+        _ => return None
+    };
+
+    Some(token)
+}
+
+fn is_unterminated (token: &Token) -> bool {
+    matches!(token.value.as_str(), ";;" | "/*" | "\"" | "'")
+}
+
+// explains why token was not recognized
+pub fn lex_error (token: &Token, severity: Severity) -> Diagnostic {
+    match token.value.as_str() {
+        ";;" => Diagnostic::new(severity, token, "unterminated comment").help("close it with `;;`"),
+        "/*" => Diagnostic::new(severity, token, "unterminated block comment").help("close it with `*/`, block comments can be nested"),
+        "\"" | "'" => Diagnostic::new(severity, token, "unterminated string literal").help(format!("close it with `{}`", token.value)),
+        x => {
+            let quoted = if x.contains('`') { format!("'{x}'") } else { format!("`{x}`") };
+            Diagnostic::new(severity, token, format!("unrecognized character{} {quoted}", if x.chars().count() > 1 { "s" } else { "" }))
+        }
+    }
+}
+
+pub fn lex_errors (tokens: &[Token], severity: Severity) -> Vec<Diagnostic> {
+    tokens.iter().filter(|x| x.typ == TokenKind::Unknown).map(|x| lex_error(x, severity)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Instant};
+    use fancy_regex::Regex;
+    use crate::lexer_rules::get_lexer_rules;
+    use super::{lex, lex_rules, Rule, TokenKind};
+
+    fn examples () -> Vec<(String, String)> {
+        let mut files: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples")).unwrap()
+            .map(|x| x.unwrap().path()).filter(|x| x.extension().is_some_and(|e| e == "alt")).collect();
+        files.sort();
+        files.into_iter().map(|x| (x.display().to_string(), fs::read_to_string(x).unwrap())).collect()
+    }
+
+    #[test]
+    fn same_tokens_as_regex_lexer () {
+        let extra = r##"
+            r#"raw "string""# "esc\"aped\\" 'single' ;; multi
+            line ;; ;;;; -5 -> x 1.25 =: y ** << >> <= >= != && || ! & ( ) #! !# and or not
+            0xFF -0b1010 0o17 1_000_000 1e-9 -2.5E+3 .5 -.25 0x 0b2 1e 1.e5 0x1.5 1__2 inf nan
+            x // line comment ;; with a half of old one
+            /* block ;; */ x
+            /// documented
+            /// function
+            fn f { } //// not a doc
+            android order nothing not2 do:add_tokens :rust!: macro:b: привет_мир x12 @ `
+            dup drop swap over rot -rot nip tuck 2dup 2drop 2dupe 2dup2 -rotate 2 dup -2drop
+            bi bi@ bi@x bi@2 bi@@ each-index each-index2 each-indexes each -index
+        "##;
+        for (name, code) in examples().into_iter().chain([("extra".to_string(), extra.to_string())]) {
+            assert_eq!(lex(code.clone(), vec![]), lex_rules(code, &get_lexer_rules()), "token streams differ for {name}");
+        }
+    }
+
+    // rule without `^` matches only where the token starts, not further in the code
+    #[test]
+    fn rules_match_at_the_current_position () {
+        let rules = [Rule { regex: Regex::new("x+").unwrap(), typ: TokenKind::String }];
+        let kinds = |tokens: Vec<super::Token>| tokens.into_iter().map(|x| (x.typ, x.value)).collect::<Vec<_>>();
+        let expected = vec![(TokenKind::Keyword, "ab".to_string()), (TokenKind::String, "xx".to_string())];
+        assert_eq!(kinds(lex("ab xx".to_string(), rules.to_vec())), expected);
+        assert_eq!(kinds(lex_rules("@ xx".to_string(), &rules)), vec![(TokenKind::Unknown, "@".to_string()), expected[1].clone()]);
+    }
+
+    #[test]
+    fn nested_comments_and_docs () {
+        let tokens = lex("/* a /* b */ ;; */ x /* /* */\n/// adds\n/// numbers\n;; old ;; fn add a b { }".to_string(), vec![]);
+        let kinds: Vec<_> = tokens.iter().map(|x| (x.typ, x.value.as_str())).collect();
+        assert_eq!(kinds[..2], [(TokenKind::Comment, "/* a /* b */ ;; */"), (TokenKind::Keyword, "x")]);
+        assert_eq!(kinds[2], (TokenKind::Unknown, "/*"));
+        let f = tokens.iter().find(|x| x.value == "fn").unwrap();
+        assert_eq!(f.doc.as_deref(), Some("adds\nnumbers"));
+    }
+
+    // run with `cargo test --release -- --ignored --nocapture lexer_benchmark`.
+    // time of hand-written lexer should grow linearly with the size of the code
+    #[test]
+    #[ignore]
+    fn lexer_benchmark () {
+        let code = examples().into_iter().map(|x| x.1).collect::<Vec<String>>().join("\n");
+
+        for n in [1, 10, 100, 1000] {
+            let code = code.repeat(n);
+            let lines = code.lines().count();
+
+            let time = Instant::now();
+            let tokens = lex(code.clone(), vec![]).len();
+            println!("hand-written lexer: {lines} lines, {tokens} tokens in {:?}", time.elapsed());
+
+            // regex rules are too slow for big inputs
+            if n > 10 { continue }
+            let time = Instant::now();
+            let tokens = lex_rules(code, &get_lexer_rules()).len();
+            println!("regex rules: {lines} lines, {tokens} tokens in {:?}", time.elapsed());
+        }
+    }
+}
@@ -3,13 +3,17 @@ use crate::lexer::{Rule, TokenKind};
 
 pub fn get_lexer_rules () -> Vec<Rule> {
     vec![
+        Rule {
+            typ: TokenKind::Keyword,
+            regex: Regex::new(r#"^(2dup|2drop|-rot|bi@|each-index)(?![A-Za-zА-Яа-я_:!0-9])"#).unwrap()
+        },
         Rule {
             typ: TokenKind::Real,
-            regex: Regex::new(r#"^(\-?[0-9]+)\.([0-9]+)"#).unwrap()
+            regex: Regex::new(r#"^\-?(([0-9][0-9_]*)?\.[0-9][0-9_]*([eE][+-]?[0-9][0-9_]*)?|[0-9][0-9_]*[eE][+-]?[0-9][0-9_]*)"#).unwrap()
         },
         Rule {
             typ: TokenKind::Int,
-            regex: Regex::new(r"^\-?[0-9]+").unwrap()
+            regex: Regex::new(r"^\-?(0x[0-9A-Fa-f_]+|0b[01_]+|0o[0-7_]+|[0-9][0-9_]*)").unwrap()
         },
         Rule {
             typ: TokenKind::String,
@@ -19,6 +23,15 @@ pub fn get_lexer_rules () -> Vec<Rule> {
             typ: TokenKind::Comment,
             regex: Regex::new(r#"(?s)^;;((.*?);;)"#).unwrap()
         },
+        Rule {
+            typ: TokenKind::Comment,
+            regex: Regex::new(r#"^//[^\n]*"#).unwrap()
+        },
+        Rule {
+            // nested block comments can't be described with regex, so rule covers only simple ones
+            typ: TokenKind::Comment,
+            regex: Regex::new(r#"(?s)^/\*((?!/\*).)*?\*/"#).unwrap()
+        },
         Rule {
             typ: TokenKind::Assign,
             regex: Regex::new(r#"^(->|\=\:)"#).unwrap()
@@ -45,7 +58,7 @@ pub fn get_lexer_rules () -> Vec<Rule> {
         },
         Rule {
             typ: TokenKind::SpecialSymbol,
-            regex: Regex::new(r#"^(\&)"#).unwrap()
+            regex: Regex::new(r#"^(\&|\(|\))"#).unwrap() // parentheses are only visual grouping
         },
         Rule {
             typ: TokenKind::Keyword,
@@ -1,54 +1,208 @@
 mod lexer;
 mod lexer_rules;
+mod diagnostic;
+mod cli;
+mod runtime;
+mod source_map;
+mod interpreter;
+mod repl;
+mod vm;
 
-use std::{collections::HashMap, fs::File, io::{Read, Write}, process::Command, path::absolute};
+// runtime of generated code, also used by interpreter
+#[path = "../cmp/src/alt/mod.rs"]
+// it's written into every generated program, which uses the parts unused here (like `pop` and `Covered`), and is checked there
+#[allow(warnings, clippy::all)]
+mod alt;
 
-use lexer::lex;
+use std::{collections::HashMap, fs::{read, read_to_string, remove_dir_all, File}, io::Write, process::{exit, Command}, path::{absolute, Path, PathBuf}};
+
+use lexer::{lex, lex_errors, lex_rules};
 use lexer_rules::get_lexer_rules;
 
 mod bytecode;
 mod transpiler;
 
 use transpiler::transpile;
-use bytecode::gen::get_all_instructions;
+use bytecode::{altc::{decode, encode}, binds::{Bind, Binds}, gen::{get_statements, LEX_WARNINGS, UNROLL_LIMIT}, item::Item, text::parse as parse_ir, value::Value};
+use diagnostic::{render_all, Diagnostic, Severity};
+use cli::{parse_args, Action, Command as AltCommand, Options, Verbosity, USAGE};
+use runtime::{materialize, temp_build_dir};
+use source_map::{map_rustc_diagnostics, SourceMap};
+use interpreter::Interpreter;
+use vm::{compile, Vm};
+
+macro_rules! verbose {
+    ($options:expr, $($arg:tt)+) => {
+        if $options.verbosity == Verbosity::Verbose { eprintln!($($arg)+); }
+    };
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Action::Compile(x)) => x,
+        Ok(Action::Help) => { println!("{USAGE}"); return }
+        Ok(Action::Version) => { println!("alt {}", env!("CARGO_PKG_VERSION")); return }
+        Err(e) => { eprintln!("error: {e}\n\nUsage: alt <command> <file.alt> [options]\nRun `alt help` for more information."); exit(2) }
+    };
 
-    let mut input_file = &String::new();
-    let mut output_file = &String::new();
-    let mut compile = false;
-    // let mut bytecode = false;
+    if options.command == AltCommand::Repl { exit(repl::repl(Some(options.input.as_str()).filter(|x| !x.is_empty()))) }
+    exit(execute(&options));
+}
+
+// runs command and returns exit code
+fn execute (options: &Options) -> i32 {
+    let input_file = &options.input;
+    // IR written by `dump-ir` (`.ir`) and modules written by `emit-bytecode` (`.altc`) are compiled as they are
+    let kind = Path::new(input_file).extension().and_then(|x| x.to_str()).unwrap_or_default();
+    if matches!(kind, "ir" | "altc") && options.command == AltCommand::DumpTokens { eprintln!("error: `{input_file}` is not alt code"); return 1 }
+    let code = if kind == "altc" { String::new() } else {
+        match read_to_string(input_file) {
+            Ok(x) => x,
+            Err(e) => { eprintln!("error: cannot read `{input_file}`: {e}"); return 1 }
+        }
+    };
 
-    if args.len() - 1 == 2 {
-        input_file = &args[1];
-        output_file = &args[2];
+    let ir = if kind == "altc" {
+        let bytes = match read(input_file) {
+            Ok(x) => x,
+            Err(e) => { eprintln!("error: cannot read `{input_file}`: {e}"); return 1 }
+        };
+        match decode(&bytes) {
+            Ok(x) => x,
+            Err(e) => { eprintln!("error: cannot load `{input_file}`: {e}"); return 1 }
+        }
+    }
+    else if kind == "ir" {
+        match parse_ir(&code) {
+            Ok(x) => x,
+            Err(e) => return fail(&[e], &code, input_file)
+        }
     }
-    else if args.len() - 1 == 3 {
-        input_file = &args[1];
-        output_file = &args[2];
-        if &args[3] == "compile" {compile = true;}
-        // else if &args[3] == "dump_bytecode" {bytecode = true;}
+    else {
+        let tokens = if options.regex_lexer { lex_rules(code.clone(), &get_lexer_rules()) } else { lex(code.clone(), vec![]) };
+        verbose!(options, "lexed `{input_file}`: {} tokens", tokens.len());
+        if options.command == AltCommand::DumpTokens {
+            for i in &tokens { println!("{}:{}\t{:?}\t{:?}", i.line, i.col, i.typ, i.value); }
+            return 0
+        }
+
+        let mut binds = Binds::new();
+        if let Some(x) = options.unroll_limit { binds.insert(UNROLL_LIMIT.to_string(), Bind::Item(Box::new(Item::Const(Value::Number(x as f64))))); }
+        if options.lex_warnings {
+            binds.insert(LEX_WARNINGS.to_string(), Bind::Item(Box::new(Item::Const(Value::Undefined))));
+            binds.warn(lex_errors(&tokens, Severity::Warning));
+        }
+        else {
+            let errors = lex_errors(&tokens, Severity::Error);
+            if !errors.is_empty() { return fail(&errors, &code, input_file) }
+        }
+
+        let ir = get_statements(&tokens, &mut binds);
+        // warnings of the program and of code lexed by the generator, like `use`d files
+        let warnings = binds.take_warnings();
+        if !warnings.is_empty() && options.verbosity > Verbosity::Quiet { eprintln!("{}", render_all(&warnings, &code, input_file)); }
+        match ir {
+            Ok(x) => x,
+            Err(e) => return fail(&e, &code, input_file)
+        }
+    };
+    verbose!(options, "generated {} statements", ir.stmts.len());
+    // spans of the IR point to alt code, which isn't known for IR
+    let code = if kind == "ir" { String::new() } else { code };
+    match options.command {
+        AltCommand::Check => { verbose!(options, "`{input_file}` has no errors"); return 0 }
+        AltCommand::DumpIr => { print!("{ir}"); return 0 }
+        AltCommand::EmitBytecode => return write_file(&options.module(), &encode(&ir)),
+        AltCommand::Disasm => { print!("{}", compile(&ir)); return 0 }
+        _ => {}
     }
 
-    let mut code = String::new();
-    let _ = File::open(absolute(input_file).unwrap()).unwrap().read_to_string(&mut code);
+    if options.command == AltCommand::Run && options.vm {
+        let program = compile(&ir);
+        verbose!(options, "compiled {} chunks for virtual machine", program.chunks.len());
+        let mut vm = Vm::new(program);
+        if let Err(e) = vm.run() { eprintln!("{}", render_all(&[e], &code, input_file)); return 101 }
+        println!("{:?}", alt::value::Value::Arr(vm.stack));
+        return 0
+    }
 
-    let tokens = lex(code, get_lexer_rules());
-    println!("{tokens:?}");
-    // dbg!(&tokens);
+    if options.command == AltCommand::Run && !options.compiled {
+        let mut interpreter = Interpreter::new();
+        if let Err(e) = interpreter.run(&ir) { eprintln!("{}", render_all(&[e], &code, input_file)); return 101 }
+        println!("{:?}", alt::value::Value::Arr(interpreter.stack));
+        return 0
+    }
 
-    let mut binds = HashMap::new();
-    let ir = get_all_instructions(tokens, &mut vec![], &mut binds);
-    dbg!(&ir);
+    let rust = transpile(ir, &mut HashMap::new());
+    if options.command == AltCommand::EmitRust {
+        match &options.output {
+            Some(path) => return write_file(path, rust.as_bytes()),
+            None => { print!("{rust}"); return 0 }
+        }
+    }
 
-    let mut binds = HashMap::new();
+    let build_dir = options.build_dir.as_ref().map(PathBuf::from).unwrap_or_else(temp_build_dir);
+    let status = build_and_run(options, &rust, &build_dir, &code);
+    if options.build_dir.is_none() { let _ = remove_dir_all(&build_dir); }
+    status
+}
 
-    let c = transpile(ir, &mut binds);
-    let _ = (File::create("./cmp/src/main.rs").unwrap()).write_all(c.as_bytes());
+// writes program with runtime into `build_dir`, compiles it there and runs if needed
+fn build_and_run (options: &Options, rust: &str, build_dir: &Path, code: &str) -> i32 {
+    let source = match materialize(build_dir, rust) {
+        Ok(x) => x,
+        Err(e) => { eprintln!("error: cannot write generated code to `{}`: {e}", build_dir.display()); return 1 }
+    };
+    verbose!(options, "wrote generated code to `{}`", source.display());
 
-    if compile {
-        let _ = Command::new("rustc")
-        .args(["./cmp/src/main.rs", "-C", "lto", "-C", "opt-level=3", "-o", absolute(output_file).unwrap().to_str().unwrap()]).status().unwrap();
+    let executable = match (options.command, &options.output) {
+        (AltCommand::Run, None) => build_dir.join("program"),
+        _ => PathBuf::from(options.executable())
+    };
+    let executable = absolute(&executable).unwrap_or(executable);
+    let status = rustc(options, build_dir, &executable, rust, code);
+    if status != 0 || options.command == AltCommand::Build {
+        if status == 0 && options.verbosity > Verbosity::Quiet { eprintln!("built `{}`", executable.display()); }
+        return status
     }
+
+    verbose!(options, "running `{}`", executable.display());
+    match Command::new(&executable).args(&options.program_args).status() {
+        Ok(x) => x.code().unwrap_or(1),
+        Err(e) => { eprintln!("error: cannot run `{}`: {e}", executable.display()); 1 }
+    }
+}
+
+// compiles generated code, reporting errors of rustc against alt source
+fn rustc (options: &Options, build_dir: &Path, executable: &Path, rust: &str, code: &str) -> i32 {
+    let mut args = vec!["main.rs".to_string(), "--error-format=json".to_string(), "-C".to_string(), format!("opt-level={}", options.opt_level), "-o".to_string(), executable.display().to_string()];
+    if options.opt_level != "0" { args.extend(["-C".to_string(), "lto".to_string()]); }
+    verbose!(options, "running `rustc {}` in `{}`", args.join(" "), build_dir.display());
+
+    let output = match Command::new("rustc").current_dir(build_dir).args(&args).output() {
+        Ok(x) => x,
+        Err(e) => { eprintln!("error: cannot run rustc: {e}"); return 1 }
+    };
+    if output.status.success() { return 0 }
+
+    let (diagnostics, unmapped) = map_rustc_diagnostics(&String::from_utf8_lossy(&output.stderr), &SourceMap::new(rust));
+    if !diagnostics.is_empty() { eprintln!("{}", render_all(&diagnostics, code, &options.input)); }
+    for i in unmapped { eprintln!("{i}"); }
+    eprintln!("error: rustc failed to compile generated code");
+    1
+}
+
+fn write_file (path: &str, content: &[u8]) -> i32 {
+    match File::create(path).and_then(|mut x| x.write_all(content)) {
+        Ok(_) => 0,
+        Err(e) => { eprintln!("error: cannot write `{path}`: {e}"); 1 }
+    }
+}
+
+fn fail (diagnostics: &[Diagnostic], code: &str, input_file: &str) -> i32 {
+    eprintln!("{}", render_all(diagnostics, code, input_file));
+    let errors = diagnostics.iter().filter(|x| x.is_error()).count();
+    eprintln!("error: could not compile `{input_file}` due to {errors} previous error{}", if errors == 1 { "" } else { "s" });
+    1
 }
@@ -0,0 +1,151 @@
+use std::{fs::read_to_string, io::{stdin, stdout, BufRead, Write}};
+
+use crate::alt::value::Value as Data;
+use crate::bytecode::{binds::Binds, gen::get_statements_on, ir::Body};
+use crate::diagnostic::{render_all, Severity};
+use crate::interpreter::Interpreter;
+use crate::lexer::{lex, lex_errors, Token, TokenKind};
+
+const HELP: &str = "\
+Type alt code to run it, stack is printed after every line.
+Commands:
+    :stack        print the stack
+    :clear        remove all values from the stack
+    :ir           print statements of the session
+    :load <file>  run code from the file
+    :help         print this message
+    :quit         exit (or Ctrl+D)";
+
+struct Session {
+    binds: Binds,
+    ir: Body,
+    interpreter: Interpreter
+}
+
+pub fn repl (file: Option<&str>) -> i32 {
+    let mut session = Session { binds: Binds::new(), ir: Body::default(), interpreter: Interpreter::new() };
+    if let Some(path) = file { session.load(path); }
+
+    println!("alt {} repl, type `:help` for commands", env!("CARGO_PKG_VERSION"));
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "alt> " } else { "...  " });
+        let _ = stdout().flush();
+        let mut line = String::new();
+        if stdin().lock().read_line(&mut line).unwrap_or(0) == 0 { println!(); return 0 }
+
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            match command {
+                ":stack" => session.print_stack(),
+                ":clear" => session.interpreter.stack.clear(),
+                ":ir" => print!("{}", session.ir),
+                ":load" if !arg.trim().is_empty() => { session.load(arg.trim()); session.print_stack(); }
+                ":load" => eprintln!("error: `:load` expects a path"),
+                ":help" => println!("{HELP}"),
+                ":quit" | ":q" => return 0,
+                x => eprintln!("error: unknown command `{x}`, type `:help` for commands")
+            }
+            continue
+        }
+
+        input += &line;
+        let tokens = lex(input.clone(), vec![]);
+        if depth(&tokens) > 0 { continue }
+        session.eval(tokens, &std::mem::take(&mut input), "<repl>");
+        session.print_stack();
+    }
+}
+
+impl Session {
+
+    fn load (&mut self, path: &str) {
+        match read_to_string(path) {
+            Ok(code) => {
+                let mut tokens = lex(code.clone(), vec![]);
+                tokens.iter_mut().for_each(|x| x.loc = path.to_string());
+                self.eval(tokens, &code, path);
+            }
+            Err(e) => eprintln!("error: cannot read `{path}`: {e}")
+        }
+    }
+
+    // generates instructions for the code on top of the current stack and runs them
+    fn eval (&mut self, tokens: Vec<Token>, code: &str, path: &str) {
+        let errors = lex_errors(&tokens, Severity::Error);
+        if !errors.is_empty() { eprintln!("{}", render_all(&errors, code, path)); return }
+
+        // a failed line changes nothing: binds and statements are kept only if it runs, the interpreter goes back to the snapshot
+        let mut binds = self.binds.clone();
+        let ir = match get_statements_on(!self.interpreter.stack.is_empty(), &tokens, &mut binds) {
+            Ok(x) => x,
+            Err(e) => { eprintln!("{}", render_all(&e, code, path)); return }
+        };
+
+        let snapshot = self.interpreter.snapshot();
+        if let Err(e) = self.interpreter.run(&ir) {
+            eprintln!("{}", render_all(&[e], code, path));
+            self.interpreter.restore(snapshot);
+            return
+        }
+        self.binds = binds;
+        self.ir.stmts.extend(ir.stmts);
+    }
+
+    fn print_stack (&self) {
+        println!("{:?}", Data::Arr(self.interpreter.stack.clone()));
+    }
+
+}
+
+// amount of unclosed brackets, input continues on the next line while it's positive
+fn depth (tokens: &[Token]) -> i32 {
+    tokens.iter().map(|x| match (x.typ, x.value.as_str()) {
+        (TokenKind::CurlyBracket | TokenKind::Bracket, "{" | "[") | (TokenKind::SpecialSymbol, "#!") => 1,
+        (TokenKind::CurlyBracket | TokenKind::Bracket, "}" | "]") | (TokenKind::SpecialSymbol, "!#") => -1,
+        _ => 0
+    }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alt::value::Value as Data;
+    use crate::bytecode::{binds::{Bind, Binds}, ir::Body, value::Value};
+    use crate::interpreter::Interpreter;
+    use crate::lexer::lex;
+    use super::Session;
+
+    fn eval (session: &mut Session, code: &str) {
+        session.eval(lex(code.to_string(), vec![]), code, "<repl>");
+    }
+
+    fn session () -> Session {
+        Session { binds: Binds::new(), ir: Body::default(), interpreter: Interpreter::new() }
+    }
+
+    #[test]
+    fn failed_line_changes_nothing () {
+        let mut session = session();
+        eval(&mut session, "1 -> x fn f { 2 } 7");
+        let ir = session.ir.to_string();
+        eval(&mut session, "3 -> x fn f { 4 } fn g { 5 } 6 5 1 do { call } call");
+        assert_eq!(session.interpreter.stack, [Data::Number(7.0)]);
+        assert_eq!(session.ir.to_string(), ir);
+        assert!(matches!(session.binds.get("x"), Some(Bind::Var(_, Some(Value::Number(x)))) if x == 1.0));
+        eval(&mut session, "x f");
+        assert_eq!(session.interpreter.stack, [Data::Number(7.0), Data::Number(1.0), Data::Number(2.0)]);
+        eval(&mut session, "g");
+        assert_eq!(session.interpreter.stack, [Data::Number(7.0), Data::Number(1.0), Data::Number(2.0)]);
+    }
+
+    // values of previous lines are popped from the stack, like in quotations
+    #[test]
+    fn lines_take_values_from_the_stack () {
+        let mut session = session();
+        eval(&mut session, "1 2");
+        eval(&mut session, "+ dup -> x 10 +");
+        assert_eq!(session.interpreter.stack, [Data::Number(13.0)]);
+        let ir = session.ir.to_string();
+        assert!(ir.contains("mov pop") && ir.contains("let x = (pop") && !ir.contains('*'), "{ir}");
+    }
+}
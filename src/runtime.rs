@@ -0,0 +1,30 @@
+use std::{fs::{create_dir_all, write}, io, path::{Path, PathBuf}};
+
+// runtime library of generated code. It's embedded into compiler, so it can be used from any directory
+pub const RUNTIME: &[(&str, &str)] = &[
+    ("alt/mod.rs", include_str!("../cmp/src/alt/mod.rs")),
+    ("alt/value.rs", include_str!("../cmp/src/alt/value.rs")),
+    ("alt/ops.rs", include_str!("../cmp/src/alt/ops.rs")),
+    ("alt/display.rs", include_str!("../cmp/src/alt/display.rs")),
+    ("alt/stack.rs", include_str!("../cmp/src/alt/stack.rs")),
+    ("alt/collections.rs", include_str!("../cmp/src/alt/collections.rs")),
+    ("alt/ref.rs", include_str!("../cmp/src/alt/ref.rs")),
+    ("alt/quote.rs", include_str!("../cmp/src/alt/quote.rs")),
+    ("alt/seq.rs", include_str!("../cmp/src/alt/seq.rs"))
+];
+
+// writes generated program and runtime into `dir`, returns path of the program
+pub fn materialize (dir: &Path, program: &str) -> io::Result<PathBuf> {
+    create_dir_all(dir.join("alt"))?;
+    for (name, code) in RUNTIME { write(dir.join(name), code)?; }
+
+    let main = dir.join("main.rs");
+    write(&main, program)?;
+    Ok(main)
+}
+
+// unique directory for one build, so concurrent builds don't clobber each other
+pub fn temp_build_dir () -> PathBuf {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|x| x.subsec_nanos()).unwrap_or(0);
+    std::env::temp_dir().join(format!("alt-build-{}-{nanos}", std::process::id()))
+}
@@ -0,0 +1,100 @@
+use fancy_regex::Regex;
+use serde_json::Value as Json;
+
+use crate::diagnostic::{Diagnostic, Severity, Span};
+
+// place in alt source where statement starts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub loc: String, pub line: usize, pub col: usize
+}
+
+// maps lines of generated code to alt source, using `/*@ line:col file */` markers emitted by transpiler
+pub struct SourceMap {
+    lines: Vec<Option<Location>>
+}
+
+impl SourceMap {
+
+    pub fn new (rust: &str) -> Self {
+        let mut current = None;
+        let lines = rust.lines().map(|line| {
+            if let Some(x) = line.rfind("/*@ ").and_then(|i| parse_marker(&line[i + 4..])) { current = Some(x); }
+            current.clone()
+        }).collect();
+
+        SourceMap { lines }
+    }
+
+    // location of 1-based line of generated code
+    pub fn get (&self, line: usize) -> Option<&Location> {
+        self.lines.get(line.checked_sub(1)?)?.as_ref()
+    }
+
+}
+
+fn parse_marker (marker: &str) -> Option<Location> {
+    let marker = &marker[..marker.find(" */")?];
+    let (pos, loc) = marker.split_once(' ').unwrap_or((marker, ""));
+    let (line, col) = pos.split_once(':')?;
+    Some(Location { loc: loc.to_string(), line: line.parse().ok()?, col: col.parse().ok()? })
+}
+
+// restores names of alt variables and functions: `_v_x` -> `x`, `_f_foo` -> `foo`
+pub fn restore_names (text: &str) -> String {
+    let re = Regex::new(r"\b_[vf]_(?=\w)").unwrap();
+    re.replace_all(text, "").to_string()
+}
+
+// converts output of `rustc --error-format=json` into diagnostics pointing to alt source.
+// messages that cannot be mapped (e.g. linker errors) are returned as rendered by rustc
+pub fn map_rustc_diagnostics (output: &str, map: &SourceMap) -> (Vec<Diagnostic>, Vec<String>) {
+    let mut diagnostics = vec![];
+    let mut unmapped = vec![];
+
+    for line in output.lines() {
+        let Ok(json) = serde_json::from_str::<Json>(line) else {
+            if !line.trim().is_empty() { unmapped.push(line.to_string()); }
+            continue
+        };
+        let severity = match json["level"].as_str() {
+            Some("error") => Severity::Error,
+            Some("warning") => Severity::Warning,
+            _ => continue
+        };
+        let message = json["message"].as_str().unwrap_or_default();
+        if message.starts_with("aborting due to") { continue }
+
+        let spans = json["spans"].as_array().cloned().unwrap_or_default();
+        let primary = spans.iter().find(|x| x["is_primary"].as_bool() == Some(true) && x["file_name"].as_str() == Some("main.rs"));
+        let location = primary.and_then(|x| map.get(x["line_start"].as_u64()? as usize));
+        let (Some(primary), Some(location)) = (primary, location) else {
+            unmapped.push(restore_names(json["rendered"].as_str().unwrap_or(message)));
+            continue
+        };
+
+        let mut diagnostic = Diagnostic {
+            severity,
+            message: restore_names(message),
+            span: Span { line: location.line, col: location.col, len: 1 },
+            loc: location.loc.as_str().into(),
+            notes: vec![],
+            help: None
+        };
+        let label = primary["label"].as_str().map(|x| format!(": {}", restore_names(x))).unwrap_or_default();
+        diagnostic = diagnostic.note(format!("in generated code at main.rs:{}:{}{label}", primary["line_start"], primary["column_start"]));
+        for child in json["children"].as_array().cloned().unwrap_or_default() {
+            let text = restore_names(child["message"].as_str().unwrap_or_default());
+            match child["level"].as_str() {
+                Some("help") => diagnostic = diagnostic.help(text),
+                _ => diagnostic = diagnostic.note(text)
+            }
+        }
+        if let Some(code) = json["code"]["code"].as_str() {
+            diagnostic = diagnostic.note(format!("rustc error {code}, see `rustc --explain {code}`"));
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    (diagnostics, unmapped)
+}
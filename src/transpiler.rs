@@ -1,13 +1,13 @@
 use std::collections::HashMap;
-use crate::bytecode::value::{ Value, is_static_array };
+use crate::bytecode::{ ir::{ Body, Expr, ExprKind, Span, Stmt, StmtKind }, value::stack_effect };
 
-pub fn transpile (instructions: Vec<Value>, binds: &mut HashMap<String, String>) -> String {
+pub fn transpile (program: Body, binds: &mut HashMap<String, String>) -> String {
     let code = format!(r#"
 #![allow(warnings, unused)]
 mod alt;
 use std::sync::{{Arc, Mutex}};
 
-use alt::{{ value::*, display::*, stack::{{ pop, push }}, collections::{{ dict }}, ops::{{ set }}, r#ref::{{Ref, Covered}} }};
+use alt::{{ value::*, display::*, stack::{{ pop, push }}, collections::{{ dict }}, ops::{{ set }}, r#ref::{{Ref, Covered}}, quote::{{Quote, curry, compose}}, seq::{{map, filter, reduce, each, each_index, any, all, find, count}} }};
 
 fn main () {{
     let mut stack: Vec<Value> = vec![];
@@ -17,149 +17,188 @@ fn main () {{
     // dump stack
     println!("{{:?}}", Value::Arr(stack));
 }}
-"#, instructions_to_code(instructions, binds, 0).join("\n"));
+"#, body_to_code(program, binds, 0, &Span::default()).join("\n"));
 
     code
 }
 
-fn instructions_to_code (instructions: Vec<Value>, binds: &mut HashMap<String, String>, mode: i32) -> Vec<String> {
+// code of statements, with marker for the source map before each statement starting at other place than the previous one
+fn body_to_code (body: Body, binds: &mut HashMap<String, String>, mode: i32, span: &Span) -> Vec<String> {
     let mut code_parts: Vec<String> = vec![];
 
-    for instruction in instructions {
-        if let Some(x) = instruction_to_code(instruction, binds, mode) {
+    let mut last = span.clone();
+    for stmt in body.stmts {
+        if stmt.span != last {
+            code_parts.push(marker(&stmt.span));
+            last = stmt.span.clone();
+        }
+        if let Some(x) = stmt_to_code(stmt, binds, mode) {
             code_parts.push(x);
         }
     }
 
-    return code_parts
+    code_parts
 }
 
-fn instruction_to_code (instruction: Value, binds: &mut HashMap<String, String>, mode: i32) -> Option<String> {
-    match instruction {
-        Value::Array(_) | Value::Number(_) | Value::String(_) | Value::Get(_) | Value::NumOp(_, _, _) | Value::Not(_) | Value::LogOp(_, _, _) | Value::Ref(_)
-        | Value::Call(_, _) | Value::Boolean(_) | Value::Dict(_, _) | Value::Pick(_, _) | Value::Type(_) | Value::RustReturnableBinding(_) => {
-            return Some(match mode {
-                3 => { format!("break 'block {};", unwrap_typed(instruction, binds)) }
-                2 => { format!("result = {};", unwrap_typed(instruction, binds)) }
-                1 => { format!("return {};", unwrap_typed(instruction, binds)) }
-                0 => { format!("push(&mut stack, {});", unwrap_typed(instruction, binds)) }
-                _ => { unwrap_typed(instruction, binds) }
-            });
-        }
-
-        Value::Block(body) => return Some(format!("{{ {} }}", instructions_to_code(body, binds, mode).join("\n"))),
-
-        Value::RefAssign(name, value) => {
-            let v = unwrap_typed(*value, binds);
-            if let Some(x) = binds.get(&name) {
-                if x == "var" { return Some(format!("*_v_{name}.lock() = {v};")) }
-                else { todo!() }
-            }
-
-            binds.insert(name.clone(), "var".to_string());
+// read by `SourceMap`
+fn marker (span: &Span) -> String {
+    if span.loc.is_empty() { return format!("/*@ {}:{} */", span.line, span.col) }
+    format!("/*@ {}:{} {} */", span.line, span.col, span.loc)
+}
 
-            return Some(format!("let mut _v_{name} = nvar!({v});"))
+fn stmt_to_code (stmt: Stmt, binds: &mut HashMap<String, String>, mode: i32) -> Option<String> {
+    let span = stmt.span;
+    match stmt.kind {
+        StmtKind::Expr(x) => {
+            Some(match mode {
+                3 => { format!("break 'block {};", unwrap_typed(x, binds)) }
+                2 => { format!("result = {};", unwrap_typed(x, binds)) }
+                1 => { format!("return {};", unwrap_typed(x, binds)) }
+                0 => { format!("push(&mut stack, {});", unwrap_typed(x, binds)) }
+                _ => { unwrap_typed(x, binds) }
+            })
         }
 
-        Value::Var(name, value) => {
-            if name == "_" { return Some(format!("let _ = {};", unwrap_typed(*value, binds))) }
-
-            if let Some(x) = binds.get(&name) {
-                if x == "var" { return Some(format!("*_v_{name}.lock() = {};", unwrap_typed(*value, binds))) }
-                else { todo!() }
-            }
+        StmtKind::Block(body) => Some(format!("{{ {} }}", body_to_code(body, &mut binds.clone(), mode, &span).join("\n"))),
 
+        StmtKind::Let(name, value) => {
+            if name == "_" { return Some(format!("let _ = {};", unwrap_typed(value, binds))) }
             binds.insert(name.clone(), "var".to_string());
-
-            return Some(format!("let mut _v_{name} = {};", parse_value_as_ref(*value, binds)))
+            Some(format!("let mut _v_{name} = {};", parse_value_as_ref(value, binds)))
         }
-        Value::Set(arr, index, value) => {
-            return Some(format!("{{let index = {}; let value = {}; set({}, index, value); }}", unwrap_typed(*index, binds), unwrap_typed(*value, binds), parse_value_as_ref(*arr, binds)))
+        StmtKind::Assign(name, value) => {
+            Some(format!("*_v_{name}.lock() = {};", unwrap_typed(value, binds)))
+        }
+        // `set` and `push` change variable itself, not its copy
+        StmtKind::Set(arr, index, value) => {
+            let arr = if let ExprKind::Get(name) = arr.kind { format!("_v_{name}.clone_ref()") } else { parse_value_as_ref(arr, binds) };
+            Some(format!("{{let index = {}; let value = {}; set({arr}, index, value); }}", unwrap_typed(index, binds), unwrap_typed(value, binds)))
         },
-        Value::Push(arr, value) => {
-            return Some(format!("{{ let value = {}; let _ = {}.push(value); }}", unwrap_typed(*value, binds), unwrap_typed(*arr, binds)))
+        StmtKind::Push(arr, value) => {
+            let arr = if let ExprKind::Get(name) = arr.kind { format!("Value::Ref(_v_{name}.clone_ref())") } else { unwrap_typed(arr, binds) };
+            Some(format!("{{ let value = {}; let _ = {arr}.push(value); }}", unwrap_typed(value, binds)))
         },
 
-        Value::If(condition, body) => {
-            let condition = match *condition {
-                Value::LogOp(_, _, _) | Value::Boolean(_) => unwrap_instruction(*condition, binds).unwrap(),
-                _ => { format!("{}.cast_bool()", unwrap_typed(*condition, binds)) }
-            };
-            return Some(format!(r#"if {condition} {{ {} }}"#, instructions_to_code(body, &mut binds.clone(), mode).join("\n")))
-        }
-        Value::Else(body) => {
-            return Some(format!(r#"else {{ {} }}"#, instructions_to_code(body, &mut binds.clone(), mode).join("\n")))
-        }
-        Value::ElseIf(condition, body) => {
-            let condition = match *condition {
-                Value::LogOp(_, _, _) | Value::Boolean(_) => unwrap_instruction(*condition, binds).unwrap(),
-                _ => { format!("{}.cast_bool()", unwrap_typed(*condition, binds)) }
-            };
-            return Some(format!(r#"else if {condition} {{ {} }}"#, instructions_to_code(body, &mut binds.clone(), mode).join("\n")))
+        StmtKind::If(branches, other) => {
+            let mut code = vec![];
+            for (condition, body) in branches {
+                let at = if condition.span != span { marker(&condition.span) } else { String::new() };
+                let condition = match condition.kind {
+                    ExprKind::Binary(op, _, _) if op.is_logical() => unwrap_instruction(condition, binds).unwrap(),
+                    ExprKind::Boolean(_) => unwrap_instruction(condition, binds).unwrap(),
+                    _ => { format!("{}.cast_bool()", unwrap_typed(condition, binds)) }
+                };
+                code.push(format!(r#"{at}if {condition} {{ {} }}"#, body_to_code(body, &mut binds.clone(), mode, &span).join("\n")));
+            }
+            if let Some(body) = other {
+                code.push(format!(r#"{{ {} }}"#, body_to_code(body, &mut binds.clone(), mode, &span).join("\n")));
+            }
+            Some(code.join(" else "))
         }
 
-        Value::Loop(body) => {
-            return Some(format!(r#"loop {{{}}}"#, instructions_to_code(body, &mut binds.clone(), mode).join("\n")))
+        StmtKind::Loop(body) => {
+            Some(format!(r#"loop {{{}}}"#, body_to_code(body, &mut binds.clone(), mode, &span).join("\n")))
         }
-        Value::Break => { return Some("break;".to_string()) }
-        Value::Continue => { return Some("continue;".to_string()) }
+        StmtKind::Break => Some("break;".to_string()),
+        StmtKind::Continue => Some("continue;".to_string()),
 
-        Value::Println(a) => { return Some(format!("println!(\"{{}}\", {});", unwrap_typed(*a, binds))) }
+        StmtKind::Println(a) => Some(format!("println!(\"{{}}\", {});", unwrap_typed(a, binds))),
 
-        Value::Fn(name, args, body) => {
+        StmtKind::Fn(name, args, body) => {
             binds.insert(name.clone(), "function".to_string());
             let binds = &mut binds.clone();
-            let r_args = || { let x = args.join(": Covered, mut _v_"); if args.len() > 0 { return format!("mut _v_{x}: Covered") } else { return x } };
+            let r_args = || { let x = args.join(": Covered, mut _v_"); if !args.is_empty() { format!("mut _v_{x}: Covered") } else { x } };
             args.iter().for_each(|i| { binds.insert(i.clone(), "var".to_string()); });
 
-            return Some(format!(r#"fn _f_{name} ({}) -> Value {{ {} Value::Empty }}"#, r_args(), instructions_to_code(body, binds, 1).join("\n")))
+            Some(format!(r#"fn _f_{name} ({}) -> Value {{ let mut stack: Vec<Value> = vec![]; {} stack.pop().unwrap_or(Value::Empty) }}"#, r_args(), body_to_code(body, binds, 1, &span).join("\n")))
         }
 
-        Value::Mov(into) => {
-            if into == "_" { return Some(format!("let _ = pop(&mut stack);")) }
+        StmtKind::Mov(into) => {
+            if into == "_" { return Some("let _ = pop(&mut stack);".to_string()) }
             binds.insert(into.clone(), "var".to_string());
-            return Some(format!(r#"let _v_{into} = pop(&mut stack);"#))
+            Some(format!(r#"let mut _v_{into} = nvar!(pop(&mut stack));"#))
         }
-        Value::RustBinding(a) => {
-            return Some(convert_rust_binding(a, binds));
+        StmtKind::Rust(a) => {
+            Some(convert_rust_binding(a, binds))
         },
-        _ => {}
+        StmtKind::StackOp(word) => {
+            let (n, result) = stack_effect(&word).unwrap();
+            let pops = (0..n).rev().map(|i| format!("let _{i} = pop(&mut stack);")).collect::<Vec<String>>().join(" ");
+            let pushes = result.iter().map(|i| format!("push(&mut stack, _{i}.clone());")).collect::<Vec<String>>().join(" ");
+            Some(format!("{{ {pops} {pushes} }}"))
+        }
+        StmtKind::CallQuote(x, args) => {
+            let args = args.into_iter().map(|x| format!("push(&mut stack, {});", unwrap_typed(x, binds))).collect::<Vec<String>>().join(" ");
+            Some(format!("{{ {args} let quote = {}; quote.call(&mut stack); }}", unwrap_typed(x, binds)))
+        }
+        StmtKind::ArrayOp(word, args) => {
+            let args = args.into_iter().enumerate().map(|(i, x)| format!("let _{i} = {};", unwrap_typed(x, binds))).collect::<Vec<String>>();
+            let names = (0..args.len()).map(|i| format!("_{i}, ")).collect::<String>();
+            Some(format!("{{ {} {}({names}&mut stack); }}", args.join(" "), word.replace('-', "_")))
+        }
     }
-    None
 }
 
-fn unwrap_typed (instruction: Value, binds: &mut HashMap<String, String>) -> String {
-    match instruction {
-        Value::Number(a) => format!("Value::Number({a:?})"),
-        Value::String(_) => format!("Value::String({})", unwrap_instruction(instruction, binds).unwrap()),
-        Value::Boolean(_) => format!("Value::Boolean({})", unwrap_instruction(instruction, binds).unwrap()),
-        Value::Get(name) => {
-            if let Some(_) = binds.get("*NO_CLONE") { return format!("_v_{name}") }
+// values of array known at compile time
+fn static_values (body: Body, binds: &mut HashMap<String, String>) -> String {
+    body.stmts.into_iter().filter_map(|x| if let StmtKind::Expr(x) = x.kind { Some(unwrap_typed(x, binds)) } else { None }).collect::<Vec<String>>().join(", ")
+}
+
+fn unwrap_typed (instruction: Expr, binds: &mut HashMap<String, String>) -> String {
+    let span = instruction.span.clone();
+    match instruction.kind {
+        ExprKind::Number(a) => format!("Value::Number({})", number_literal(a)),
+        ExprKind::String(_) => format!("Value::String({})", unwrap_instruction(instruction, binds).unwrap()),
+        ExprKind::Boolean(_) => format!("Value::Boolean({})", unwrap_instruction(instruction, binds).unwrap()),
+        ExprKind::Get(name) => {
+            if binds.contains_key("*NO_CLONE") { return format!("_v_{name}") }
             format!("_v_{name}.clone()")
         },
-        Value::LogOp(_, _, _) | Value::Not(_) => format!("Value::Boolean({})", unwrap_instruction(instruction, binds).unwrap()),
-        Value::NumOp(a, b, op) => {
-            macro_rules! m { () => { return format!("({} {op} {})", unwrap_typed(*a, binds), unwrap_typed(*b, binds)); }; }
-            match *a { Value::Get(_) | Value::NumOp(_, _, _) | Value::LogOp(_, _, _) => { m!(); } _ => {} }
-            match *b { Value::Get(_) | Value::NumOp(_, _, _) | Value::LogOp(_, _, _) => { m!(); } _ => {} }
-            return format!("Value::Number({})", unwrap_instruction(Value::NumOp(a, b, op), binds).unwrap())
+        ExprKind::Binary(op, _, _) if op.is_logical() => format!("Value::Boolean({})", unwrap_instruction(instruction, binds).unwrap()),
+        ExprKind::Not(_) => format!("Value::Boolean({})", unwrap_instruction(instruction, binds).unwrap()),
+        ExprKind::Binary(op, a, b) => {
+            if op.symbol() == "**" { return format!("{}.pow({})", unwrap_typed(*a, binds), unwrap_typed(*b, binds)) }
+            format!("({} {} {})", unwrap_typed(*a, binds), op.symbol(), unwrap_typed(*b, binds))
+        },
+        ExprKind::Array(body) => {
+            if body.is_static() {
+                return format!("Value::Arr(vec![{}])", static_values(body, binds));
+            }
+            format!("{{ let mut stack: Vec<Value> = vec![]; {} Value::Arr(stack) }}", body_to_code(body, &mut binds.clone(), 0, &span).join("\n"))
+        },
+        ExprKind::Call(_, _) => unwrap_instruction(instruction, binds).unwrap(),
+        ExprKind::Block(body) => format!("'block: {{ {} break 'block Value::Empty; }}", body_to_code(body, &mut binds.clone(), 3, &span).join("\n")),
+        ExprKind::Dict(k, v) => {
+            let (k, v) = (Expr::new(ExprKind::Array(k), span.clone()), Expr::new(ExprKind::Array(v), span));
+            format!("Value::Dict(dict({}, {}))", unwrap_instruction(k, binds).unwrap(), unwrap_instruction(v, binds).unwrap())
+        },
+        ExprKind::Pick(arr, index) => format!("{}[{}].clone()", unwrap_typed(*arr, binds), unwrap_typed(*index, binds)),
+        ExprKind::Type(_) => format!("Value::String({})", unwrap_instruction(instruction, binds).unwrap()),
+        ExprKind::Rust(a) => format!("{{ {} }}", convert_rust_binding(a, binds)),
+
+        ExprKind::Quote(body) => {
+            // closure gets its own references to variables, so they are shared with the code around it
+            let mut names = vec![];
+            body.stmts.iter().for_each(|x| captured(x, binds, &mut names));
+            let captures = names.iter().map(|x| format!("let _v_{x} = _v_{x}.clone_ref();")).collect::<Vec<String>>().join(" ");
+            format!("{{ {captures} Value::Quote(Quote::new(move |stack: &mut Vec<Value>| {{ let mut stack = stack; {} }})) }}", body_to_code(body, &mut binds.clone(), 0, &span).join("\n"))
         },
-        Value::Array(body) => {
-            println!("{body:?}");
-            if is_static_array(&body) {
-                return format!("Value::Arr(vec![{}])", instructions_to_code(body, binds, -1).join(", "));
+        ExprKind::Builtin(word, args) => {
+            let mut args = args.into_iter().map(|x| unwrap_typed(x, binds));
+            let a = args.next().unwrap();
+            match word.as_str() {
+                "sum" => format!("{a}.sum()"),
+                "concat" => format!("{a}.concat({})", args.next().unwrap()),
+                "flat" => format!("{a}.flat()"),
+                "inc" | "dec" => format!("{{ let mut x = {a}; x.{word}(); x }}"),
+                "int" => format!("Value::Number({a}.cast_int() as f64)"),
+                _ => format!("Value::Arr({a}.cast_vec())")
             }
-            format!("{{ let mut stack: Vec<Value> = vec![]; {} Value::Arr(stack) }}", instructions_to_code(body, &mut binds.clone(), 0).join("\n"))
         },
-        Value::Call(_, _) => unwrap_instruction(instruction, binds).unwrap(),
-        Value::Block(body) => format!("'block: {{ {} break 'block Value::Empty; }}", instructions_to_code(body, binds, 3).join("\n")),
-        Value::Dict(k, v) => format!("Value::Dict(dict({}, {}))", unwrap_instruction(Value::Array(k), binds).unwrap(), unwrap_instruction(Value::Array(v), binds).unwrap()),
-        Value::Pick(arr, index) => format!("{}[{}].clone()", unwrap_typed(*arr, binds), unwrap_typed(*index, binds)),
-        Value::Type(_) => format!("Value::String({})", unwrap_instruction(instruction, binds).unwrap()),
-        Value::RustReturnableBinding(a) => format!("{{ {} }}", convert_rust_binding(a, binds)),
-
-        Value::Ref(x) => {
-            if let Value::Get(x) = *x {
+        ExprKind::Curry(x, q) => format!("curry({}, {})", unwrap_typed(*x, binds), unwrap_typed(*q, binds)),
+        ExprKind::Compose(p, q) => format!("compose({}, {})", unwrap_typed(*p, binds), unwrap_typed(*q, binds)),
+        ExprKind::Ref(x) => {
+            if let ExprKind::Get(x) = x.kind {
                 format!("Value::Ref(_v_{x}.clone_ref())")
             }
             else {
@@ -167,40 +206,34 @@ fn unwrap_typed (instruction: Value, binds: &mut HashMap<String, String>) -> Str
             }
         },
 
-        _ => "Value::Undefined".to_string()
+        ExprKind::Undefined => "Value::Undefined".to_string()
     }
 }
 
-fn unwrap_instruction (instruction: Value, binds: &mut HashMap<String, String>) -> Option<String> {
-    match instruction {
-        Value::Number(a) => { return Some(format!("{a:?}")) }
-        Value::String(a) => { return Some(format!("\"{a}\".to_string()")) }
-        Value::Boolean(a) => { return Some(format!("{a:?}")) }
+fn unwrap_instruction (instruction: Expr, binds: &mut HashMap<String, String>) -> Option<String> {
+    let span = instruction.span;
+    match instruction.kind {
+        ExprKind::Number(a) => { return Some(number_literal(a)) }
+        ExprKind::String(a) => { return Some(format!("{a:?}.to_string()")) }
+        ExprKind::Boolean(a) => { return Some(format!("{a:?}")) }
         
-        Value::NumOp(a, b, op) => {
-            let a = convert_number(*a, binds);
-            let b = convert_number(*b, binds);
-            if op == "<<" || op == ">>" { return Some(format!("((({a} as i64) {op} ({b} as i64)) as f64)")) }
-
-            return Some(format!("({a} {op} {b})"))
-        }
-
-        Value::LogOp(a, b, op) => {
+        ExprKind::Binary(op, a, b) if op.is_logical() => {
             let x;
             let y;
-            match op.as_str() {
+            match op.symbol() {
                 "<" | ">" | "<=" | ">=" => {x = convert_number(*a, binds); y = convert_number(*b, binds);}
+                "&&" | "||" => {x = convert_bool(*a, binds); y = convert_bool(*b, binds);}
                 _ => {x = unwrap_typed(*a, binds); y = unwrap_typed(*b, binds);}
             }
-            if op == "=" { return Some(format!("({x} == {y})")) }
+            if op.symbol() == "=" { return Some(format!("({x} == {y})")) }
 
-            return Some(format!("({x} {op} {y})"))
+            return Some(format!("({x} {} {y})", op.symbol()))
         }
-        Value::Not(a) => { return Some(format!("!({})", unwrap_instruction(*a, binds).unwrap())) }
+        ExprKind::Not(a) => { return Some(format!("!({})", convert_bool(*a, binds))) }
 
-        Value::Get(name) => { return Some(format!("_v_{name}")) }
-        Value::Ref(x) => {
-            if let Value::Get(x) = *x {
+        ExprKind::Get(name) => { return Some(format!("_v_{name}")) }
+        ExprKind::Ref(x) => {
+            if let ExprKind::Get(x) = x.kind {
                 return Some(format!("_v_{x}"))
             }
             else {
@@ -208,56 +241,96 @@ fn unwrap_instruction (instruction: Value, binds: &mut HashMap<String, String>)
             }
         }
 
-        Value::Call(name, args) => { return Some(format!("_f_{name}({})", args.iter().map(|i| parse_value_as_ref(i.clone(), binds)).collect::<Vec<String>>().join(", "))) }
-        Value::Array(body) => {
-            if is_static_array(&body) {
-                return Some(format!("vec![{}]", instructions_to_code(body, binds, -1).join(", ")));
+        ExprKind::Call(name, args) => { return Some(format!("_f_{name}({})", args.into_iter().map(|i| parse_value_as_ref(i, binds)).collect::<Vec<String>>().join(", "))) }
+        ExprKind::Array(body) => {
+            if body.is_static() {
+                return Some(format!("vec![{}]", static_values(body, binds)));
             }
-            return Some(format!("{{ let mut stack: Vec<Value> = vec![]; {} stack }}", instructions_to_code(body, &mut binds.clone(), 0).join("\n")));
+            return Some(format!("{{ let mut stack: Vec<Value> = vec![]; {} stack }}", body_to_code(body, &mut binds.clone(), 0, &span).join("\n")));
         }
-        Value::Type(a) => { return Some(format!("{}.cast_type()", unwrap_typed(*a, binds))) },
+        ExprKind::Type(a) => { return Some(format!("{}.cast_type()", unwrap_typed(*a, binds))) },
         _ => {}
     }
     None
 }
 
-fn parse_value_as_ref (instruction: Value, binds: &mut HashMap<String, String>) -> String {
-    if let Value::Ref(x) = instruction.clone() {
-        if let Value::Get(x) = *x {
+// variables of enclosing code used by the statement
+fn captured (stmt: &Stmt, binds: &HashMap<String, String>, names: &mut Vec<String>) {
+    if let StmtKind::Let(x, _) | StmtKind::Assign(x, _) = &stmt.kind { capture(x, binds, names); }
+    // branches in their order
+    if let StmtKind::If(branches, other) = &stmt.kind {
+        for (condition, body) in branches {
+            captured_expr(condition, binds, names);
+            body.stmts.iter().for_each(|x| captured(x, binds, names));
+        }
+        other.iter().flat_map(|x| &x.stmts).for_each(|x| captured(x, binds, names));
+        return
+    }
+    let (exprs, bodies) = stmt.children();
+    exprs.into_iter().for_each(|x| captured_expr(x, binds, names));
+    bodies.into_iter().flat_map(|x| &x.stmts).for_each(|x| captured(x, binds, names));
+}
+
+fn captured_expr (expr: &Expr, binds: &HashMap<String, String>, names: &mut Vec<String>) {
+    if let ExprKind::Get(x) = &expr.kind { capture(x, binds, names); }
+    let (exprs, bodies) = expr.children();
+    exprs.into_iter().for_each(|x| captured_expr(x, binds, names));
+    bodies.into_iter().flat_map(|x| &x.stmts).for_each(|x| captured(x, binds, names));
+}
+
+fn capture (name: &String, binds: &HashMap<String, String>, names: &mut Vec<String>) {
+    if binds.get(name).is_some_and(|v| v == "var") && !names.contains(name) { names.push(name.clone()) }
+}
+
+fn number_literal (a: f64) -> String {
+    if a.is_nan() { return "f64::NAN".to_string() }
+    if a.is_infinite() { return format!("{}f64::INFINITY", if a < 0.0 { "-" } else { "" }) }
+    format!("{a:?}")
+}
+
+fn parse_value_as_ref (instruction: Expr, binds: &mut HashMap<String, String>) -> String {
+    if let ExprKind::Ref(x) = &instruction.kind {
+        if let ExprKind::Get(x) = &x.kind {
             return format!("_v_{x}.clone_ref()")
         }
     }
-    return format!("nvar!({})", unwrap_typed(instruction, binds))
+    format!("nvar!({})", unwrap_typed(instruction, binds))
 }
 
-fn convert_number (instruction: Value, binds: &mut HashMap<String, String>) -> String {
-    match instruction {
-        Value::NumOp(_, _, _) | Value::Number(_) => unwrap_instruction(instruction, binds).unwrap(),
-        Value::String(_) | Value::Array(_) => format!("{}.len() as f64", unwrap_instruction(instruction, binds).unwrap()),
+fn convert_number (instruction: Expr, binds: &mut HashMap<String, String>) -> String {
+    match instruction.kind {
+        ExprKind::Number(_) => unwrap_instruction(instruction, binds).unwrap(),
+        ExprKind::String(_) | ExprKind::Array(_) => format!("{}.len() as f64", unwrap_instruction(instruction, binds).unwrap()),
         _ => format!("{}.cast_float()", unwrap_typed(instruction, binds))
     }
 }
 
-fn convert_rust_binding (binding: Vec<Value>, binds: &mut HashMap<String, String>) -> String {
+fn convert_bool (instruction: Expr, binds: &mut HashMap<String, String>) -> String {
+    match instruction.kind {
+        ExprKind::Binary(op, _, _) if op.is_logical() => unwrap_instruction(instruction, binds).unwrap(),
+        ExprKind::Not(_) | ExprKind::Boolean(_) => unwrap_instruction(instruction, binds).unwrap(),
+        _ => format!("{}.cast_bool()", unwrap_typed(instruction, binds))
+    }
+}
+
+fn convert_rust_binding (binding: Vec<Expr>, binds: &mut HashMap<String, String>) -> String {
     let mut res: Vec<String> = vec![];
     
-    let mut i = 0;
-    while i < binding.len() {
-        match &binding[i] {
-            Value::String(a) => { res.push(a.replace(r#"\""#, r#"""#)); }
-            Value::Get(a) => { res.push(format!("_v_{a}")) }
-            Value::Ref(a) => {
-                if let Value::String(x) = *a.clone() {
-                    res.push(format!("\"{x}\""));
+    for i in binding {
+        match i.kind {
+            ExprKind::String(a) => { res.push(a); }
+            ExprKind::Get(a) => { res.push(format!("_v_{a}")) }
+            ExprKind::Ref(a) => {
+                if let ExprKind::String(x) = a.kind {
+                    res.push(format!("{x:?}"));
                 }
                 else {
-                    res.push(instruction_to_code(*a.clone(), binds, -1).unwrap());
+                    res.push(unwrap_typed(*a, binds));
                 }
             }
-            _ => { res.push(instruction_to_code(binding[i].clone(), binds, -1).unwrap()) }
+            _ => { res.push(unwrap_typed(i, binds)) }
         }
-        i += 1;
     }
 
-    return res.join("")
-}
\ No newline at end of file
+    res.join("")
+}
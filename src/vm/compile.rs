@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::alt::value::Value as Data;
+use crate::bytecode::ir::{BinOp, Body, Expr, ExprKind, Stmt, StmtKind};
+use super::op::{Chunk, Op, Program};
+
+/* Compiles the IR into chunks of instructions. Names are resolved at compile time: variables become slots of the chunk,
+functions become chunks. Quotation gets its own slots for variables it uses from the chunk creating it, which are
+copied when it's created, like the interpreter copies variables visible where quotation is created */
+
+// what to do with values of statements, like in the interpreter. `Yield` jumps to the end of the block used as value
+#[derive(Clone, Copy)]
+enum Mode {
+    Push, Return, Yield(usize)
+}
+
+// where `break` and `continue` go
+enum Target {
+    Loop { start: usize, breaks: Vec<usize> },
+    // array body stops, like in the interpreter
+    Array(Vec<usize>),
+    // block used as value, where they are errors
+    Value
+}
+
+// chunk which is compiled
+struct Context {
+    chunk: usize,
+    scopes: Vec<HashMap<String, u32>>,
+    targets: Vec<Target>,
+    // quotation uses variables of the context around it
+    quote: bool
+}
+
+struct Compiler {
+    program: Program,
+    contexts: Vec<Context>,
+    // functions visible in each scope: chunk and count of arguments
+    functions: Vec<HashMap<String, (u32, usize)>>,
+    // all functions, for the ones the interpreter finds because their definition ran before
+    all: HashMap<String, (u32, usize)>,
+    // jumps to patch for each block used as value
+    yields: Vec<Vec<usize>>
+}
+
+pub fn compile (program: &Body) -> Program {
+    let mut compiler = Compiler { program: Program::default(), contexts: vec![], functions: vec![], all: HashMap::new(), yields: vec![] };
+    compiler.chunk("main".to_string(), &[], false, |c| c.body(program, Mode::Push));
+    compiler.program
+}
+
+impl Compiler {
+
+    // compiles chunk into a new context and returns its number
+    fn chunk (&mut self, name: String, args: &[String], quote: bool, f: impl FnOnce(&mut Self)) -> u32 {
+        let chunk = self.program.chunks.len();
+        self.program.chunks.push(Chunk { name, ..Chunk::default() });
+        self.fill(chunk, args, quote, f);
+        chunk as u32
+    }
+
+    fn fill (&mut self, chunk: usize, args: &[String], quote: bool, f: impl FnOnce(&mut Self)) {
+        self.program.chunks[chunk].args = args.len();
+        self.program.chunks[chunk].slots = args.to_vec();
+        let scope = args.iter().enumerate().map(|(i, x)| (x.clone(), i as u32)).collect();
+        self.contexts.push(Context { chunk, scopes: vec![scope], targets: vec![], quote });
+        f(self);
+        self.emit(Op::End);
+        self.contexts.pop();
+    }
+
+    fn context (&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn code (&mut self) -> &mut Vec<Op> {
+        let chunk = self.context().chunk;
+        &mut self.program.chunks[chunk].code
+    }
+
+    fn emit (&mut self, op: Op) -> usize {
+        self.code().push(op);
+        self.code().len() - 1
+    }
+
+    fn here (&mut self) -> usize {
+        self.code().len()
+    }
+
+    // sets target of jump to the current instruction
+    fn patch (&mut self, jump: usize) {
+        let to = self.here() as u32;
+        match &mut self.code()[jump] {
+            Op::Jump(x) | Op::JumpIf(x) | Op::JumpIfNot(x) => *x = to,
+            _ => unreachable!()
+        }
+    }
+
+    fn constant (&mut self, x: Data) -> Op {
+        let consts = &mut self.program.consts;
+        let i = consts.iter().position(|c| same(c, &x)).unwrap_or_else(|| { consts.push(x); consts.len() - 1 });
+        Op::Const(i as u32)
+    }
+
+    fn word (&mut self, word: &str) -> u32 {
+        let words = &mut self.program.words;
+        words.iter().position(|x| x == word).unwrap_or_else(|| { words.push(word.to_string()); words.len() - 1 }) as u32
+    }
+
+    fn fail (&mut self, message: String, help: Option<&str>) {
+        self.program.errors.push((message, help.map(|x| x.to_string())));
+        let i = self.program.errors.len() - 1;
+        self.emit(Op::Fail(i as u32));
+    }
+
+    // new slot of the chunk of context `i`
+    fn slot (&mut self, i: usize, name: &str) -> u32 {
+        let slots = &mut self.program.chunks[self.contexts[i].chunk].slots;
+        slots.push(name.to_string());
+        slots.len() as u32 - 1
+    }
+
+    fn declare (&mut self, name: &str) -> u32 {
+        let slot = self.slot(self.contexts.len() - 1, name);
+        self.context().scopes.last_mut().unwrap().insert(name.to_string(), slot);
+        slot
+    }
+
+    fn resolve (&mut self, name: &str) -> Option<u32> {
+        self.resolve_in(self.contexts.len() - 1, name)
+    }
+
+    // slot of variable visible in context `i`, captured from the contexts around quotations
+    fn resolve_in (&mut self, i: usize, name: &str) -> Option<u32> {
+        if let Some(x) = self.contexts[i].scopes.iter().rev().find_map(|x| x.get(name)) { return Some(*x) }
+        if !self.contexts[i].quote { return None }
+        let outer = self.resolve_in(i - 1, name)?;
+        let slot = self.slot(i, name);
+        self.program.chunks[self.contexts[i].chunk].captures.push((outer, slot));
+        self.contexts[i].scopes[0].insert(name.to_string(), slot);
+        Some(slot)
+    }
+
+    fn body (&mut self, body: &Body, mode: Mode) {
+        // functions can be called before their definition
+        let mut functions = HashMap::new();
+        for i in &body.stmts {
+            if let StmtKind::Fn(name, args, _) = &i.kind {
+                self.program.chunks.push(Chunk { name: name.clone(), ..Chunk::default() });
+                let function = (self.program.chunks.len() as u32 - 1, args.len());
+                functions.insert(name.clone(), function);
+                self.all.insert(name.clone(), function);
+            }
+        }
+        self.functions.push(functions);
+        for i in &body.stmts { self.statement(i, mode); }
+        self.functions.pop();
+    }
+
+    fn scoped (&mut self, body: &Body, mode: Mode) {
+        self.context().scopes.push(HashMap::new());
+        self.body(body, mode);
+        self.context().scopes.pop();
+    }
+
+    fn statement (&mut self, stmt: &Stmt, mode: Mode) {
+        let (here, chunk) = (self.here(), self.context().chunk);
+        self.program.chunks[chunk].spans.push((here, stmt.span.clone()));
+
+        match &stmt.kind {
+            StmtKind::Expr(x) => {
+                self.expr(x);
+                match mode {
+                    Mode::Push => { self.emit(Op::Keep); }
+                    Mode::Return => { self.emit(Op::Ret); }
+                    Mode::Yield(i) => { let jump = self.emit(Op::Jump(0)); self.yields[i].push(jump); }
+                }
+            }
+
+            StmtKind::Block(body) => self.scoped(body, mode),
+
+            StmtKind::Let(name, value) => {
+                if name == "_" { self.expr(value); self.emit(Op::Pop); return }
+                self.cell(value);
+                let slot = self.declare(name);
+                self.emit(Op::Bind(slot));
+            }
+            StmtKind::Assign(name, value) => {
+                self.expr(value);
+                let slot = match self.resolve(name) { Some(x) => x, None => self.declare(name) };
+                self.emit(Op::Store(slot));
+            }
+            StmtKind::Set(arr, index, value) => {
+                self.expr(index);
+                self.expr(value);
+                match &arr.kind {
+                    ExprKind::Get(name) => self.load(name, Op::Cell),
+                    _ => self.cell(arr)
+                }
+                self.emit(Op::Set);
+            }
+            StmtKind::Push(arr, value) => {
+                self.expr(value);
+                match &arr.kind {
+                    ExprKind::Get(name) => self.load(name, Op::LoadRef),
+                    _ => self.expr(arr)
+                }
+                self.emit(Op::Push);
+            }
+
+            StmtKind::If(branches, other) => {
+                let mut ends = vec![];
+                for (condition, body) in branches {
+                    self.expr(condition);
+                    let next = self.emit(Op::JumpIfNot(0));
+                    self.scoped(body, mode);
+                    ends.push(self.emit(Op::Jump(0)));
+                    self.patch(next);
+                }
+                if let Some(body) = other { self.scoped(body, mode); }
+                for i in ends { self.patch(i); }
+            }
+            StmtKind::Loop(body) => {
+                let start = self.here();
+                self.context().targets.push(Target::Loop { start, breaks: vec![] });
+                self.scoped(body, mode);
+                self.emit(Op::Jump(start as u32));
+                let Some(Target::Loop { breaks, .. }) = self.context().targets.pop() else { unreachable!() };
+                for i in breaks { self.patch(i); }
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let jump = self.here();
+                let is_break = stmt.kind == StmtKind::Break;
+                match self.context().targets.last_mut() {
+                    Some(Target::Loop { start, .. }) if !is_break => { let start = *start as u32; self.emit(Op::Jump(start)); }
+                    Some(Target::Loop { breaks: jumps, .. } | Target::Array(jumps)) => { jumps.push(jump); self.emit(Op::Jump(0)); }
+                    Some(Target::Value) => self.fail("`break` and `continue` cannot be used in blocks used as values".to_string(), None),
+                    // stops the chunk, like the interpreter stops the body
+                    None => { self.emit(Op::End); }
+                }
+            }
+
+            StmtKind::Println(x) => { self.expr(x); self.emit(Op::Print); }
+            StmtKind::Fn(name, args, body) => {
+                let chunk = self.functions.iter().rev().find_map(|x| x.get(name)).unwrap().0 as usize;
+                self.fill(chunk, args, false, |c| c.scoped(body, Mode::Return));
+            }
+
+            StmtKind::Mov(name) => {
+                if name == "_" { self.emit(Op::Drop); return }
+                let slot = self.declare(name);
+                self.emit(Op::Mov(slot));
+            }
+            StmtKind::StackOp(word) => { let word = self.word(word); self.emit(Op::Shuffle(word)); }
+            StmtKind::CallQuote(x, args) => {
+                for i in args { self.expr(i); self.emit(Op::Keep); }
+                self.expr(x);
+                self.emit(Op::CallQuote);
+            }
+            StmtKind::ArrayOp(word, args) => {
+                for i in args { self.expr(i); }
+                let word = self.word(word);
+                self.emit(Op::ArrayWord(word, args.len() as u32));
+            }
+            StmtKind::Rust(_) => self.fail("`:rust!` cannot be interpreted".to_string(), Some("compile the program: `alt run --compiled`"))
+        }
+    }
+
+    // instruction with slot of the variable, or error if it isn't defined
+    fn load (&mut self, name: &str, op: fn(u32) -> Op) {
+        match self.resolve(name) {
+            Some(x) => { self.emit(op(x)); }
+            None => self.fail(format!("variable `{name}` is not defined here"), None)
+        }
+    }
+
+    // pushes to `cells` the variable itself for `x &`, new variable for any other value
+    fn cell (&mut self, expr: &Expr) {
+        if let ExprKind::Ref(x) = &expr.kind {
+            if let ExprKind::Get(name) = &x.kind { return self.load(name, Op::Cell) }
+        }
+        self.expr(expr);
+        self.emit(Op::NewCell);
+    }
+
+    fn expr (&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Number(x) => { let x = self.constant(Data::Number(*x)); self.emit(x); }
+            ExprKind::String(x) => { let x = self.constant(Data::String(x.clone())); self.emit(x); }
+            ExprKind::Boolean(x) => { let x = self.constant(Data::Boolean(*x)); self.emit(x); }
+            ExprKind::Undefined => { let x = self.constant(Data::Undefined); self.emit(x); }
+            ExprKind::Get(name) => self.load(name, Op::Load),
+
+            ExprKind::Binary(op @ (BinOp::And | BinOp::Or), a, b) => {
+                self.expr(a);
+                let short = self.emit(if *op == BinOp::And { Op::JumpIfNot(0) } else { Op::JumpIf(0) });
+                self.expr(b);
+                self.emit(Op::Bool);
+                let end = self.emit(Op::Jump(0));
+                self.patch(short);
+                let x = self.constant(Data::Boolean(*op == BinOp::Or));
+                self.emit(x);
+                self.patch(end);
+            }
+            ExprKind::Binary(op, a, b) => { self.expr(a); self.expr(b); self.emit(Op::Binary(*op)); }
+            ExprKind::Not(x) => { self.expr(x); self.emit(Op::Not); }
+
+            ExprKind::Ref(x) => match &x.kind {
+                ExprKind::Get(name) => self.load(name, Op::LoadRef),
+                _ => { self.expr(x); self.emit(Op::Ref); }
+            },
+            ExprKind::Call(name, args) => {
+                let function = self.functions.iter().rev().find_map(|x| x.get(name)).or_else(|| self.all.get(name)).copied();
+                let Some((chunk, n)) = function else { return self.fail(format!("unknown function `{name}`"), None) };
+                let args = &args[..n.min(args.len())];
+                for i in args { self.cell(i); }
+                self.emit(Op::Call(chunk, args.len() as u32));
+            }
+
+            ExprKind::Array(body) => self.array(body),
+            ExprKind::Block(body) => {
+                self.yields.push(vec![]);
+                self.context().targets.push(Target::Value);
+                self.scoped(body, Mode::Yield(self.yields.len() - 1));
+                self.context().targets.pop();
+                let x = self.constant(Data::Empty);
+                self.emit(x);
+                for i in self.yields.pop().unwrap() { self.patch(i); }
+            }
+            ExprKind::Dict(k, v) => { self.array(k); self.array(v); self.emit(Op::MakeDict); }
+            ExprKind::Pick(arr, index) => { self.expr(arr); self.expr(index); self.emit(Op::Pick); }
+            ExprKind::Type(x) => { self.expr(x); self.emit(Op::Type); }
+            ExprKind::Rust(_) => self.fail("`:rust!:` cannot be interpreted".to_string(), Some("compile the program: `alt run --compiled`")),
+            ExprKind::Builtin(word, args) => {
+                for i in args { self.expr(i); }
+                self.emit(match word.as_str() {
+                    "sum" => Op::Sum,
+                    "concat" => Op::Concat,
+                    "flat" => Op::Flat,
+                    "inc" => Op::Inc,
+                    "dec" => Op::Dec,
+                    "int" => Op::Int,
+                    _ => Op::ToArray
+                });
+            }
+            ExprKind::Curry(x, q) => { self.expr(x); self.expr(q); self.emit(Op::Curry); }
+            ExprKind::Compose(p, q) => { self.expr(p); self.expr(q); self.emit(Op::Compose); }
+            ExprKind::Quote(body) => {
+                // targets of `break` don't cross the quotation, the chunk stops instead
+                let chunk = self.chunk("do".to_string(), &[], true, |c| c.body(body, Mode::Push));
+                self.emit(Op::Quote(chunk));
+            }
+        }
+    }
+
+    fn array (&mut self, body: &Body) {
+        if body.is_static() {
+            for i in &body.stmts { if let StmtKind::Expr(x) = &i.kind { self.expr(x); } }
+            self.emit(Op::MakeArray(body.stmts.len() as u32));
+            return
+        }
+
+        self.emit(Op::BeginArray);
+        self.context().targets.push(Target::Array(vec![]));
+        self.scoped(body, Mode::Push);
+        let Some(Target::Array(ends)) = self.context().targets.pop() else { unreachable!() };
+        for i in ends { self.patch(i); }
+        self.emit(Op::EndArray);
+    }
+
+}
+
+// constants are shared only if they are the same, `0` and `-0` aren't
+fn same (a: &Data, b: &Data) -> bool {
+    match (a, b) {
+        (Data::Number(a), Data::Number(b)) => a.to_bits() == b.to_bits(),
+        (Data::String(a), Data::String(b)) => a == b,
+        (Data::Boolean(a), Data::Boolean(b)) => a == b,
+        (Data::Undefined, Data::Undefined) | (Data::Empty, Data::Empty) => true,
+        _ => false
+    }
+}
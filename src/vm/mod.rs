@@ -0,0 +1,341 @@
+use std::{cell::RefCell, mem::take, rc::Rc};
+
+use crate::alt::{seq, value::Value as Data, r#ref::Ref, quote::{Quote, curry, compose}, collections::dict, ops::set};
+use crate::bytecode::{ir::{BinOp, Span}, value::{stack_effect, values}};
+use crate::diagnostic::{self, Diagnostic, Severity};
+use crate::interpreter::cell;
+
+pub mod op;
+mod compile;
+
+pub use compile::compile;
+use op::{Op, Program};
+
+/* Stack-based virtual machine running instructions compiled from the IR (see `op.rs`), with the same semantics as the
+interpreter and generated code, so it's used instead of them to run programs quickly */
+
+// chunk which runs and its variables
+struct Frame {
+    chunk: usize,
+    ip: usize,
+    slots: Vec<Option<Ref>>
+}
+
+pub struct Vm {
+    pub stack: Vec<Data>,
+    program: Rc<Program>,
+    values: Vec<Data>,
+    cells: Vec<Ref>,
+    frames: Vec<Frame>,
+    // stacks of callers and arrays which are made
+    saved: Vec<Vec<Data>>,
+    // error inside of quotation, which can't return it through the call
+    error: Rc<RefCell<Option<Diagnostic>>>
+}
+
+impl Vm {
+
+    pub fn new (program: Program) -> Self {
+        Self::with(Rc::new(program), vec![], Rc::new(RefCell::new(None)))
+    }
+
+    fn with (program: Rc<Program>, stack: Vec<Data>, error: Rc<RefCell<Option<Diagnostic>>>) -> Self {
+        Self { stack, program, values: vec![], cells: vec![], frames: vec![], saved: vec![], error }
+    }
+
+    // runs the program from its first instruction
+    pub fn run (&mut self) -> Result<(), Diagnostic> {
+        self.execute(0, vec![])
+    }
+
+    // runs chunk until its end, with its first slots given
+    fn execute (&mut self, chunk: usize, given: Vec<Option<Ref>>) -> Result<(), Diagnostic> {
+        self.call(chunk, given);
+        let result = self.step();
+        // stack of the program is the one before calls and arrays which failed
+        if result.is_err() && !self.saved.is_empty() { self.stack = self.saved.swap_remove(0); }
+        result
+    }
+
+    fn call (&mut self, chunk: usize, mut slots: Vec<Option<Ref>>) {
+        slots.resize_with(self.program.chunks[chunk].slots.len(), || None);
+        self.frames.push(Frame { chunk, ip: 0, slots });
+    }
+
+    // returns from function with its value, restoring stack of the caller
+    fn ret (&mut self, x: Data) {
+        self.frames.pop();
+        self.stack = self.saved.pop().unwrap();
+        self.values.push(x);
+    }
+
+    fn step (&mut self) -> Result<(), Diagnostic> {
+        let program = self.program.clone();
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = program.chunks[frame.chunk].code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Const(x) => self.values.push(program.consts[x as usize].clone()),
+                Op::Load(x) => { let x = self.slot(x)?.lock().clone(); self.values.push(x); }
+                Op::LoadRef(x) => { let x = self.slot(x)?; self.values.push(Data::Ref(x)); }
+                Op::Ref => { let x = self.pop(); self.values.push(Data::Ref(cell(x))); }
+                Op::Cell(x) => { let x = self.slot(x)?; self.cells.push(x); }
+                Op::NewCell => { let x = self.pop(); self.cells.push(cell(x)); }
+                Op::Bind(x) => { let x = (x as usize, self.cells.pop()); self.frame().slots[x.0] = x.1; }
+                Op::Store(x) => {
+                    let value = self.pop();
+                    match &self.frame().slots[x as usize] {
+                        Some(var) => *var.lock() = value,
+                        None => self.frame().slots[x as usize] = Some(cell(value))
+                    }
+                }
+                Op::Pop => { self.pop(); }
+
+                Op::Binary(op) => {
+                    let (y, x) = (self.pop(), self.pop());
+                    self.values.push(match op {
+                        BinOp::Add => x + y, BinOp::Sub => x - y, BinOp::Mul => x * y, BinOp::Div => x / y, BinOp::Rem => x % y,
+                        BinOp::Shl => x << y, BinOp::Shr => x >> y, BinOp::Pow => x.pow(y),
+                        BinOp::Eq => Data::Boolean(x == y),
+                        BinOp::Ne => Data::Boolean(x != y),
+                        BinOp::Lt => Data::Boolean(x.cast_float() < y.cast_float()),
+                        BinOp::Gt => Data::Boolean(x.cast_float() > y.cast_float()),
+                        BinOp::Le => Data::Boolean(x.cast_float() <= y.cast_float()),
+                        _ => Data::Boolean(x.cast_float() >= y.cast_float())
+                    });
+                }
+                Op::Not => { let x = self.pop(); self.values.push(Data::Boolean(!x.cast_bool())); }
+                Op::Bool => { let x = self.pop(); self.values.push(Data::Boolean(x.cast_bool())); }
+
+                Op::Jump(x) => self.frame().ip = x as usize,
+                Op::JumpIf(x) => if self.pop().cast_bool() { self.frame().ip = x as usize },
+                Op::JumpIfNot(x) => if !self.pop().cast_bool() { self.frame().ip = x as usize },
+                Op::Call(chunk, n) => {
+                    let args = self.cells.split_off(self.cells.len() - n as usize).into_iter().map(Some).collect();
+                    self.saved.push(take(&mut self.stack));
+                    self.call(chunk as usize, args);
+                }
+                Op::Ret => { let x = self.pop(); self.ret(x); }
+                Op::End => {
+                    if self.frames.len() == 1 { self.frames.pop(); return Ok(()) }
+                    // function which doesn't return a value gives the top of its stack
+                    let x = self.stack.pop().unwrap_or(Data::Empty);
+                    self.ret(x);
+                }
+
+                Op::MakeArray(n) => { let x = self.values.split_off(self.values.len() - n as usize); self.values.push(Data::Arr(x)); }
+                Op::BeginArray => self.saved.push(take(&mut self.stack)),
+                Op::EndArray => { let x = std::mem::replace(&mut self.stack, self.saved.pop().unwrap()); self.values.push(Data::Arr(x)); }
+                Op::MakeDict => {
+                    let (v, k) = (self.pop().cast_vec(), self.pop().cast_vec());
+                    if k.len() != v.len() { return Err(self.error(format!("failed to create dictionary: shapes [{}] and [{}] don't match", k.len(), v.len()))) }
+                    self.values.push(Data::Dict(dict(k, v)));
+                }
+                Op::Pick => { let (index, arr) = (self.pop(), self.pop()); self.values.push(arr[index].clone()); }
+                Op::Set => {
+                    let (value, index) = (self.pop(), self.pop());
+                    set(self.cells.pop().unwrap(), index, value);
+                }
+                Op::Push => { let (mut arr, value) = (self.pop(), self.pop()); arr.push(value); }
+                Op::Type => { let x = self.pop(); self.values.push(Data::String(x.cast_type())); }
+                Op::Sum => { let x = self.pop(); self.values.push(x.sum()); }
+                Op::Concat => { let (b, a) = (self.pop(), self.pop()); self.values.push(a.concat(b)); }
+                Op::Flat => { let x = self.pop(); self.values.push(x.flat()); }
+                Op::Inc => { let mut x = self.pop(); x.inc(); self.values.push(x); }
+                Op::Dec => { let mut x = self.pop(); x.dec(); self.values.push(x); }
+                Op::Int => { let x = self.pop(); self.values.push(Data::Number(x.cast_int() as f64)); }
+                Op::ToArray => { let x = self.pop(); self.values.push(Data::Arr(x.cast_vec())); }
+
+                Op::Print => println!("{}", self.pop()),
+                Op::Keep => { let x = self.pop(); if x != Data::Empty { self.stack.push(x); } }
+                Op::Mov(x) => {
+                    let Some(value) = self.stack.pop() else { return Err(self.error("stack underflow: not enough values on the stack")) };
+                    self.frame().slots[x as usize] = Some(cell(value));
+                }
+                Op::Drop => if self.stack.pop().is_none() { return Err(self.error("stack underflow: not enough values on the stack")) },
+                Op::Shuffle(x) => {
+                    let word = &program.words[x as usize];
+                    let (n, result) = stack_effect(word).unwrap();
+                    if self.stack.len() < n { return Err(self.error(format!("stack underflow: `{word}` expects {}", values(n)))) }
+                    let values = self.stack.split_off(self.stack.len() - n);
+                    self.stack.extend(result.iter().map(|i| values[*i].clone()));
+                }
+                Op::Quote(chunk) => {
+                    // quotation runs in its own machine, sharing variables it captures
+                    let captures = &program.chunks[chunk as usize].captures;
+                    let mut slots: Vec<Option<Ref>> = captures.iter().map(|_| None).collect();
+                    slots.resize_with(captures.iter().map(|x| x.1 as usize + 1).max().unwrap_or(0), || None);
+                    for (outer, inner) in captures { slots[*inner as usize] = self.frame().slots[*outer as usize].as_ref().map(|x| x.clone_ref()); }
+                    let (program, error) = (self.program.clone(), self.error.clone());
+                    self.values.push(Data::Quote(Quote::new(move |stack| {
+                        let mut vm = Vm::with(program.clone(), take(stack), error.clone());
+                        let result = vm.execute(chunk as usize, slots.iter().map(|x| x.as_ref().map(|x| x.clone_ref())).collect());
+                        *stack = vm.stack;
+                        if let Err(e) = result { error.borrow_mut().get_or_insert(e); }
+                    })));
+                }
+                Op::CallQuote => {
+                    let x = self.pop();
+                    self.callable(x)?.call(&mut self.stack);
+                    if let Some(e) = self.error.borrow_mut().take() { return Err(e) }
+                }
+                Op::ArrayWord(x, n) => {
+                    let mut args = self.values.split_off(self.values.len() - n as usize);
+                    let q = Data::Quote(self.callable(args.pop().unwrap())?);
+                    let (arr, stack) = (args.remove(0), &mut self.stack);
+                    match program.words[x as usize].as_str() {
+                        "map" => seq::map(arr, q, stack),
+                        "filter" => seq::filter(arr, q, stack),
+                        "reduce" => seq::reduce(arr, args.remove(0), q, stack),
+                        "each" => seq::each(arr, q, stack),
+                        "each-index" => seq::each_index(arr, q, stack),
+                        "any" => seq::any(arr, q, stack),
+                        "all" => seq::all(arr, q, stack),
+                        "find" => seq::find(arr, q, stack),
+                        _ => seq::count(arr, q, stack)
+                    }
+                    if let Some(e) = self.error.borrow_mut().take() { return Err(e) }
+                }
+                Op::Curry => { let (q, x) = (self.pop(), self.pop()); self.values.push(curry(x, self.quoted(q))); }
+                Op::Compose => { let (q, p) = (self.pop(), self.pop()); self.values.push(compose(self.quoted(p), self.quoted(q))); }
+                Op::Fail(x) => {
+                    let (message, help) = &program.errors[x as usize];
+                    let e = self.error(message.as_str());
+                    return Err(match help { Some(x) => e.help(x.as_str()), None => e })
+                }
+            }
+        }
+    }
+
+    fn frame (&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    // operands are always pushed by instructions before
+    fn pop (&mut self) -> Data {
+        self.values.pop().unwrap()
+    }
+
+    fn slot (&mut self, x: u32) -> Result<Ref, Diagnostic> {
+        match &self.frame().slots[x as usize] {
+            Some(x) => Ok(x.clone_ref()),
+            None => {
+                let frame = self.frames.last().unwrap();
+                let name = &self.program.chunks[frame.chunk].slots[x as usize];
+                Err(self.error(format!("variable `{name}` is not defined here")))
+            }
+        }
+    }
+
+    // quotation to call, generated code panics on other values
+    fn callable (&self, mut x: Data) -> Result<Quote, Diagnostic> {
+        while let Data::Ref(r) = x { x = r.clone(); }
+        match x {
+            Data::Quote(x) => Ok(x),
+            x => Err(self.error(format!("cannot call {}", x.cast_type())).help("create quotation with `do {...}`"))
+        }
+    }
+
+    // operand of `curry` and `compose`. other values than quotations give the error of `call` when the result is called
+    fn quoted (&self, x: Data) -> Data {
+        let Err(e) = self.callable(x.clone()) else { return x };
+        let error = self.error.clone();
+        Data::Quote(Quote::new(move |_| { error.borrow_mut().get_or_insert(e.clone()); }))
+    }
+
+    // error at the statement of the instruction which runs now
+    fn error (&self, message: impl Into<String>) -> Diagnostic {
+        let frame = self.frames.last().unwrap();
+        let Span { loc, line, col } = self.program.chunks[frame.chunk].span(frame.ip - 1);
+        Diagnostic { severity: Severity::Error, message: message.into(), span: diagnostic::Span { line, col, len: 1 }, loc, notes: vec![], help: None }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{read_dir, read_to_string}, path::Path};
+    use crate::{interpreter::Interpreter, lexer::lex};
+    use crate::bytecode::{binds::Binds, gen::get_statements, ir::Body};
+    use super::{compile, Vm};
+
+    fn generate (code: &str) -> Body {
+        get_statements(&lex(code.to_string(), vec![]), &mut Binds::new()).unwrap()
+    }
+
+    // stack left by the program, or error with its position
+    fn interpret (ir: &Body) -> String {
+        let mut interpreter = Interpreter::new();
+        match interpreter.run(ir) {
+            Ok(_) => format!("{:?}", interpreter.stack),
+            Err(e) => format!("{}:{} {} {:?} {:?}", e.span.line, e.span.col, e.message, e.help, interpreter.stack)
+        }
+    }
+
+    fn execute (ir: &Body) -> String {
+        let mut vm = Vm::new(compile(ir));
+        match vm.run() {
+            Ok(_) => format!("{:?}", vm.stack),
+            Err(e) => format!("{}:{} {} {:?} {:?}", e.span.line, e.span.col, e.message, e.help, vm.stack)
+        }
+    }
+
+    #[test]
+    fn runs_like_the_interpreter () {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut programs: Vec<String> = read_dir(root.join("examples")).unwrap().map(|x| read_to_string(x.unwrap().path()).unwrap()).collect();
+        programs.extend([
+            "fn fact n { n 1 <= if { 1 } else { n 1 - fact n * } } 10 fact",
+            "0 -> i loop { i 1 + -> i i 2 % 0 = if { continue } i 7 > if { break } i }",
+            "[ 1 2 3 ] -> a a 0 10 set a 4 push a a do { 2 * } map a 0 do { + } reduce",
+            "5 -> k [ 1 2 3 ] do { k + } map [ 1 2 3 ] do { 1 > } filter [ 1 2 3 ] do { 2 > } find",
+            "[ 0 -> j loop { j 1 + -> j j 5 > if { break } j } ] { 1 2 }",
+            "0 -> c do { c 1 + -> c } -> bump bump call bump call c",
+            "1 2 3 rot swap dup over nip tuck",
+            "later fn later { [ 1 2 ] [ 3 ] concat flat sum }",
+            "fn pair a b { [ a b ] } 1 2 pair \"abc\" type",
+            "1 2 3 drop drop drop drop",
+            "5 call",
+            "[ 1 2 ] do { x } map",
+            "fn f q { 1 q curry call } 5 f",
+            "fn g q { do { 1 } q compose call } [ 2 ] g"
+        ].map(|x| x.to_string()));
+
+        for code in programs {
+            let Ok(ir) = get_statements(&lex(code.clone(), vec![]), &mut Binds::new()) else { continue };
+            assert_eq!(execute(&ir), interpret(&ir), "{code}");
+        }
+    }
+
+    #[test]
+    fn disassembles_instructions () {
+        let text = compile(&generate("fn twice n { n 2 * }\n0 -> c\ndo { c twice -> c } -> q")).to_string();
+        assert_eq!(text, "\
+chunk 0: main
+     0  2:1     const       1           ; 0
+     1          new-cell
+     2          bind        0           ; c
+     3  3:1     quote       2
+     4          new-cell
+     5          bind        1           ; q
+     6          end
+
+chunk 1: twice n
+     0  1:14    load-ref    0           ; n
+     1          const       0           ; 2
+     2          binary                  ; *
+     3          ret
+     4          end
+
+chunk 2: do
+        capture 0 0                     ; c
+     0  3:6     load        0           ; c
+     1          new-cell
+     2          call        1 1         ; twice
+     3          store       0           ; c
+     4          end
+");
+    }
+}
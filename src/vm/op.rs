@@ -0,0 +1,177 @@
+use std::fmt;
+
+use crate::alt::value::Value as Data;
+use crate::bytecode::ir::{BinOp, Span};
+
+/* Instructions of the virtual machine. It has three stacks: `values` are operands of instructions, `stack` is the stack
+of the program (the one printed after `alt run`), `cells` are variables passed to instructions which bind or change them.
+Every function and quotation is a chunk of its own, chunk 0 is the program. Variables are slots of the chunk */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    // pushes constant
+    Const(u32),
+    // pushes value of the variable, or reference to it
+    Load(u32), LoadRef(u32),
+    // pushes reference to new variable with popped value
+    Ref,
+    // pushes variable itself to `cells`, or new variable with popped value
+    Cell(u32), NewCell,
+    // declares variable from `cells`
+    Bind(u32),
+    // changes variable to popped value, declares it if it isn't declared
+    Store(u32),
+    // drops popped value
+    Pop,
+    Binary(BinOp), Not,
+    // casts popped value to boolean
+    Bool,
+    Jump(u32), JumpIf(u32), JumpIfNot(u32),
+    // calls chunk with arguments from `cells`
+    Call(u32, u32),
+    // returns popped value from function
+    Ret,
+    // end of chunk: function returns the top of its stack, program and quotation stop
+    End,
+    // makes array of popped values
+    MakeArray(u32),
+    // starts stack for values of array body, makes array of them
+    BeginArray, EndArray,
+    // makes dictionary of popped keys and values
+    MakeDict,
+    Pick,
+    // sets index of collection from `cells` to value
+    Set,
+    Push,
+    Type, Sum, Concat, Flat, Inc, Dec, Int, ToArray,
+    Print,
+    // pushes popped value to `stack`
+    Keep,
+    // declares variable with value popped from `stack`, or drops it
+    Mov(u32), Drop,
+    // stack shuffling word
+    Shuffle(u32),
+    // makes quotation of chunk, sharing variables it captures
+    Quote(u32),
+    CallQuote,
+    // higher-order array word with count of its operands
+    ArrayWord(u32, u32),
+    Curry, Compose,
+    // raises error
+    Fail(u32)
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    pub chunks: Vec<Chunk>,
+    pub consts: Vec<Data>,
+    // words of stack shuffling and array instructions
+    pub words: Vec<String>,
+    // messages of `Fail` and their help
+    pub errors: Vec<(String, Option<String>)>
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    // `main`, name of function, or `do` for quotation
+    pub name: String,
+    // arguments are the first slots
+    pub args: usize,
+    pub code: Vec<Op>,
+    // names of variables
+    pub slots: Vec<String>,
+    // slots of the chunk creating quotation which are copied to slots of quotation
+    pub captures: Vec<(u32, u32)>,
+    // first instruction of every statement and its span
+    pub spans: Vec<(usize, Span)>
+}
+
+impl Chunk {
+    // span of the statement the instruction belongs to
+    pub fn span (&self, ip: usize) -> Span {
+        let i = self.spans.partition_point(|x| x.0 <= ip);
+        if i == 0 { return Span::default() }
+        self.spans[i - 1].1.clone()
+    }
+}
+
+// disassembler: one line for every instruction, with span of statement it starts and meaning of its operands
+impl fmt::Display for Program {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, chunk) in self.chunks.iter().enumerate() {
+            if n > 0 { writeln!(f)?; }
+            let args = chunk.slots[..chunk.args].iter().map(|x| format!(" {x}")).collect::<String>();
+            writeln!(f, "chunk {n}: {}{args}", chunk.name)?;
+            for (a, b) in &chunk.captures { writeln!(f, "{:<40}; {}", format!("        capture {a} {b}"), chunk.slots[*b as usize])?; }
+
+            let mut spans = chunk.spans.iter().peekable();
+            for (ip, op) in chunk.code.iter().enumerate() {
+                let mut span = String::new();
+                while let Some((_, x)) = spans.next_if(|x| x.0 <= ip) { span = format!("{}:{}", x.line, x.col); }
+                let (name, operands, note) = self.describe(chunk, *op);
+                let line = format!("{ip:>6}  {span:<8}{name:<12}{operands}");
+                match note {
+                    Some(note) => writeln!(f, "{line:<40}; {note}")?,
+                    None => writeln!(f, "{}", line.trim_end())?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+
+    // mnemonic, operands and what they mean
+    fn describe (&self, chunk: &Chunk, op: Op) -> (&'static str, String, Option<String>) {
+        let slot = |x: u32| Some(chunk.slots[x as usize].clone());
+        let (name, operands, note) = match op {
+            Op::Const(x) => ("const", vec![x], Some(format!("{:?}", self.consts[x as usize]))),
+            Op::Load(x) => ("load", vec![x], slot(x)),
+            Op::LoadRef(x) => ("load-ref", vec![x], slot(x)),
+            Op::Ref => ("ref", vec![], None),
+            Op::Cell(x) => ("cell", vec![x], slot(x)),
+            Op::NewCell => ("new-cell", vec![], None),
+            Op::Bind(x) => ("bind", vec![x], slot(x)),
+            Op::Store(x) => ("store", vec![x], slot(x)),
+            Op::Pop => ("pop", vec![], None),
+            Op::Binary(x) => ("binary", vec![], Some(x.symbol().to_string())),
+            Op::Not => ("not", vec![], None),
+            Op::Bool => ("bool", vec![], None),
+            Op::Jump(x) => ("jump", vec![x], None),
+            Op::JumpIf(x) => ("jump-if", vec![x], None),
+            Op::JumpIfNot(x) => ("jump-if-not", vec![x], None),
+            Op::Call(x, n) => ("call", vec![x, n], Some(self.chunks[x as usize].name.clone())),
+            Op::Ret => ("ret", vec![], None),
+            Op::End => ("end", vec![], None),
+            Op::MakeArray(n) => ("make-array", vec![n], None),
+            Op::BeginArray => ("begin-array", vec![], None),
+            Op::EndArray => ("end-array", vec![], None),
+            Op::MakeDict => ("make-dict", vec![], None),
+            Op::Pick => ("pick", vec![], None),
+            Op::Set => ("set", vec![], None),
+            Op::Push => ("push", vec![], None),
+            Op::Type => ("type", vec![], None),
+            Op::Sum => ("sum", vec![], None),
+            Op::Concat => ("concat", vec![], None),
+            Op::Flat => ("flat", vec![], None),
+            Op::Inc => ("inc", vec![], None),
+            Op::Dec => ("dec", vec![], None),
+            Op::Int => ("int", vec![], None),
+            Op::ToArray => ("to-array", vec![], None),
+            Op::Print => ("print", vec![], None),
+            Op::Keep => ("keep", vec![], None),
+            Op::Mov(x) => ("mov", vec![x], slot(x)),
+            Op::Drop => ("drop", vec![], None),
+            Op::Shuffle(x) => ("stack", vec![x], Some(self.words[x as usize].clone())),
+            Op::Quote(x) => ("quote", vec![x], None),
+            Op::CallQuote => ("call-quote", vec![], None),
+            Op::ArrayWord(x, n) => ("array", vec![x, n], Some(self.words[x as usize].clone())),
+            Op::Curry => ("curry", vec![], None),
+            Op::Compose => ("compose", vec![], None),
+            Op::Fail(x) => ("fail", vec![x], Some(self.errors[x as usize].0.clone()))
+        };
+        (name, operands.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" "), note)
+    }
+
+}